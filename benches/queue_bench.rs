@@ -0,0 +1,212 @@
+//! Queue throughput and worker pool benchmarks.
+//!
+//! Run with `cargo bench --bench queue_bench`. Criterion prints the mean
+//! time per iteration with a confidence interval, and on later runs the
+//! change relative to the previous run. HTML reports are written under
+//! `target/criterion/`.
+//!
+//! Every benchmark uses `HardProblem` with a zero delay, so the numbers
+//! measure queue and dispatch overhead rather than handler work. The
+//! push/pop cases are per 10,000 tasks and the handle/pool cases per 1,000.
+//! Comparing the two worker pool cases shows how much extra workers help
+//! (or how much they fight over the queue lock) when tasks are tiny.
+//!
+//! The contended cases run 8 producers and 8 consumers moving 10,000 tasks
+//! through one `Mutex<PriorityQueue>` and through a `ShardedPriorityQueue`
+//! with 8 shards.
+
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use task_handler::sharded_queue::ShardedPriorityQueue;
+use task_handler::worker::WorkerPool;
+use task_handler::{HardProblem, PriorityLevel, PriorityQueue, Task, TaskQueue};
+
+const QUEUE_TASKS: usize = 10_000;
+const HANDLED_TASKS: usize = 1_000;
+const CONTENDED_THREADS: usize = 8;
+
+fn task(i: usize) -> Task {
+    let priority_level = match i % 3 {
+        0 => PriorityLevel::High,
+        1 => PriorityLevel::Medium,
+        _ => PriorityLevel::Low,
+    };
+    Task::new(
+        HardProblem::new(i as i32, 1).with_delay(Duration::ZERO),
+        priority_level,
+    )
+}
+
+fn tasks(n: usize) -> Vec<Task> {
+    (0..n).map(task).collect()
+}
+
+fn queue_of(n: usize) -> PriorityQueue {
+    let mut queue = PriorityQueue::new();
+    for task in tasks(n) {
+        queue.push(task);
+    }
+    queue
+}
+
+fn bench_push_1000(c: &mut Criterion) {
+    c.bench_function("push_10000", |b| {
+        b.iter_batched(
+            || tasks(QUEUE_TASKS),
+            |tasks| {
+                let mut queue = PriorityQueue::new();
+                for task in tasks {
+                    queue.push(task);
+                }
+                queue
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_pop_1000(c: &mut Criterion) {
+    c.bench_function("pop_10000", |b| {
+        b.iter_batched(
+            || queue_of(QUEUE_TASKS),
+            |mut queue| {
+                while let Some(task) = queue.pop_simple() {
+                    black_box(task);
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_push_pop_interleaved(c: &mut Criterion) {
+    c.bench_function("push_pop_interleaved_10000", |b| {
+        b.iter_batched(
+            || tasks(QUEUE_TASKS),
+            |tasks| {
+                let mut queue = PriorityQueue::new();
+                for (i, task) in tasks.into_iter().enumerate() {
+                    queue.push(task);
+                    if i % 2 == 1 {
+                        black_box(queue.pop_simple());
+                    }
+                }
+                queue
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_handle_sync_1000(c: &mut Criterion) {
+    c.bench_function("handle_blocking_1000", |b| {
+        b.iter_batched(
+            || queue_of(HANDLED_TASKS),
+            |mut queue| queue.handle_blocking(),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_worker_pool(c: &mut Criterion, n_workers: usize) {
+    c.bench_function(&format!("worker_pool_{}_workers_1000", n_workers), |b| {
+        b.iter_batched(
+            || {
+                let pool = WorkerPool::new(n_workers);
+                for task in tasks(HANDLED_TASKS) {
+                    pool.submit(task);
+                }
+                pool
+            },
+            |pool| pool.run(),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_worker_pool_2_workers(c: &mut Criterion) {
+    bench_worker_pool(c, 2);
+}
+
+fn bench_worker_pool_8_workers(c: &mut Criterion) {
+    bench_worker_pool(c, 8);
+}
+
+/// Runs `CONTENDED_THREADS` producers, each pushing its share of `tasks`,
+/// alongside as many consumers popping until every task has been taken.
+fn contended<P, O>(tasks: Vec<Task>, push: P, pop: O)
+where
+    P: Fn(Task) + Sync,
+    O: Fn() -> Option<Task> + Sync,
+{
+    let total = tasks.len();
+    let popped = AtomicUsize::new(0);
+    let mut shares: Vec<Vec<Task>> = (0..CONTENDED_THREADS).map(|_| Vec::new()).collect();
+    for (i, task) in tasks.into_iter().enumerate() {
+        shares[i % CONTENDED_THREADS].push(task);
+    }
+    thread::scope(|scope| {
+        for share in shares {
+            let push = &push;
+            scope.spawn(move || share.into_iter().for_each(push));
+        }
+        for _ in 0..CONTENDED_THREADS {
+            scope.spawn(|| {
+                while popped.load(Ordering::Relaxed) < total {
+                    if let Some(task) = pop() {
+                        black_box(task);
+                        popped.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+}
+
+fn bench_contended_unsharded(c: &mut Criterion) {
+    c.bench_function("contended_unsharded_10000", |b| {
+        b.iter_batched(
+            || tasks(QUEUE_TASKS),
+            |tasks| {
+                let queue = Mutex::new(PriorityQueue::new());
+                contended(
+                    tasks,
+                    |task| queue.lock().unwrap().push(task),
+                    || queue.lock().unwrap().pop_simple(),
+                );
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_contended_sharded(c: &mut Criterion) {
+    c.bench_function("contended_sharded_8_shards_10000", |b| {
+        b.iter_batched(
+            || tasks(QUEUE_TASKS),
+            |tasks| {
+                let queue = ShardedPriorityQueue::new(CONTENDED_THREADS);
+                contended(tasks, |task| queue.push(task), || queue.pop());
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_push_1000,
+    bench_pop_1000,
+    bench_push_pop_interleaved,
+    bench_handle_sync_1000,
+    bench_worker_pool_2_workers,
+    bench_worker_pool_8_workers,
+    bench_contended_unsharded,
+    bench_contended_sharded
+);
+criterion_main!(benches);
@@ -0,0 +1,47 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use task_handler::{PriorityLevel, PriorityQueue, Task, TaskError, TaskHandler, TaskQueue};
+
+const TASKS: usize = 1000;
+
+struct Noop;
+
+impl TaskHandler for Noop {
+    fn execute(&self) -> Result<i32, TaskError> {
+        Ok(0)
+    }
+}
+
+fn queue_of(n: usize) -> PriorityQueue {
+    let mut queue = PriorityQueue::new();
+    for i in 0..n {
+        let priority_level = match i % 3 {
+            0 => PriorityLevel::High,
+            1 => PriorityLevel::Medium,
+            _ => PriorityLevel::Low,
+        };
+        queue.push(Task::new(Noop, priority_level));
+    }
+    queue
+}
+
+fn bench_rayon_vs_handle(c: &mut Criterion) {
+    let mut group = c.benchmark_group("execute_1000");
+    group.bench_function("execute_par", |b| {
+        b.iter_batched(
+            || queue_of(TASKS),
+            |queue| queue.execute_par(),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("handle", |b| {
+        b.iter_batched(
+            || queue_of(TASKS),
+            |mut queue| queue.handle(),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_rayon_vs_handle);
+criterion_main!(benches);
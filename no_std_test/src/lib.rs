@@ -0,0 +1,77 @@
+//! Checks that the core types work without `std`.
+
+#![no_std]
+
+extern crate alloc;
+#[cfg(test)]
+extern crate std;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+
+use task_handler::{
+    PriorityLevel, PriorityQueue, Summary, Task, TaskError, TaskHandler, TaskQueue,
+};
+use uuid::Uuid;
+
+pub struct Constant(pub i32);
+
+impl TaskHandler for Constant {
+    fn execute(&self) -> Result<i32, TaskError> {
+        Ok(self.0)
+    }
+}
+
+pub struct Failing;
+
+impl TaskHandler for Failing {
+    fn execute(&self) -> Result<i32, TaskError> {
+        Err(TaskError::Failed(String::from("failing")))
+    }
+}
+
+/// Without `std` there is no random `Uuid`, so ids come from a counter.
+pub fn task(
+    id: u128,
+    handler: impl TaskHandler + Send + Sync + 'static,
+    priority_level: PriorityLevel,
+) -> Task {
+    Task::with_id(Uuid::from_u128(id), Box::new(handler), priority_level)
+}
+
+/// Drains `queue` on the current thread.
+pub fn drain(queue: &mut PriorityQueue) -> Summary {
+    queue.handle_blocking()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn pops_by_priority_then_fifo() {
+        let mut queue = PriorityQueue::new();
+        queue.push(task(1, Constant(1), PriorityLevel::Low));
+        queue.push(task(2, Constant(2), PriorityLevel::High));
+        queue.push(task(3, Constant(3), PriorityLevel::Low));
+        queue.push(task(4, Constant(4), PriorityLevel::Medium));
+
+        let order: Vec<u128> = core::iter::from_fn(|| queue.pop_simple())
+            .map(|task| task.id.as_u128())
+            .collect();
+        assert_eq!(order, [2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn handle_blocking_counts_outcomes() {
+        let mut queue = PriorityQueue::new();
+        queue.push(task(1, Constant(1), PriorityLevel::High));
+        queue.push(task(2, Failing, PriorityLevel::Low));
+
+        let summary = drain(&mut queue);
+        assert_eq!((summary.executed, summary.failed), (1, 1));
+        assert!(queue.is_empty());
+    }
+}
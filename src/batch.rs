@@ -0,0 +1,154 @@
+use std::sync::{Arc, Mutex};
+
+use crate::executor::{TaskContext, TaskOutcome};
+use crate::{catch_panics, PriorityQueue, Task, TaskError, TaskQueue};
+
+/// Handles several queued tasks in one call, e.g. a bulk insert. Returns one
+/// result per context, in the same order.
+pub trait BatchTaskHandler: Send + Sync {
+    fn execute_batch(&self, tasks: &[&TaskContext]) -> Vec<Result<i32, TaskError>>;
+}
+
+const DEFAULT_BATCH_SIZE: usize = 16;
+
+/// Drains a queue in batches of up to `batch_size` tasks and hands each batch
+/// to a single [`BatchTaskHandler`]. Queued tasks only contribute their
+/// context; their own handlers are not called.
+pub struct BatchWorkerPool {
+    handler: Arc<dyn BatchTaskHandler>,
+    queue: Arc<Mutex<PriorityQueue>>,
+    batch_size: usize,
+}
+
+impl BatchWorkerPool {
+    pub fn new(handler: impl BatchTaskHandler + 'static) -> Self {
+        BatchWorkerPool {
+            handler: Arc::new(handler),
+            queue: Arc::new(Mutex::new(PriorityQueue::new())),
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    pub fn with_batch_size(mut self, n: usize) -> Self {
+        self.batch_size = n.max(1);
+        self
+    }
+
+    pub fn submit(&self, task: Task) {
+        self.queue.lock().unwrap().push(task);
+    }
+
+    /// Runs batches until the queue is empty and returns every task's
+    /// outcome in the order the tasks were taken from the queue.
+    pub fn run(&self) -> Vec<TaskOutcome> {
+        let mut outcomes = Vec::new();
+        loop {
+            let batch = self.next_batch();
+            if batch.is_empty() {
+                return outcomes;
+            }
+
+            let contexts: Vec<&TaskContext> = batch.iter().collect();
+            // A panic fails every task in the batch, not the whole run.
+            let mut batch_results = Vec::new();
+            let caught = catch_panics(|| {
+                batch_results = self.handler.execute_batch(&contexts);
+                Ok(0)
+            });
+            if let Err(panic) = caught {
+                batch_results = vec![Err(panic); contexts.len()];
+            }
+            let mut results = batch_results.into_iter();
+            for ctx in batch {
+                let result = results.next().unwrap_or_else(|| {
+                    Err(TaskError::Failed("batch handler returned no result".into()))
+                });
+                outcomes.push(TaskOutcome {
+                    task_id: ctx.task_id,
+                    result,
+                    output: None,
+                });
+            }
+        }
+    }
+
+    fn next_batch(&self) -> Vec<TaskContext> {
+        let mut queue = self.queue.lock().unwrap();
+        let mut batch = Vec::with_capacity(self.batch_size);
+        while batch.len() < self.batch_size {
+            let Some(task) = queue.pop_simple() else {
+                break;
+            };
+            batch.push(TaskContext::for_task(&task));
+        }
+        batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{HardProblem, PriorityLevel};
+
+    /// Returns each task's index within its batch, and counts the batches.
+    #[derive(Default)]
+    struct BatchIndex {
+        batches: Arc<AtomicUsize>,
+    }
+
+    impl BatchTaskHandler for BatchIndex {
+        fn execute_batch(&self, tasks: &[&TaskContext]) -> Vec<Result<i32, TaskError>> {
+            self.batches.fetch_add(1, Ordering::SeqCst);
+            (0..tasks.len() as i32).map(Ok).collect()
+        }
+    }
+
+    struct Panics;
+
+    impl BatchTaskHandler for Panics {
+        fn execute_batch(&self, _: &[&TaskContext]) -> Vec<Result<i32, TaskError>> {
+            panic!("batch panic")
+        }
+    }
+
+    fn submit_tasks(pool: &BatchWorkerPool, n: usize) -> Vec<uuid::Uuid> {
+        (0..n)
+            .map(|_| {
+                let task = Task::new(HardProblem::new(1, 2), PriorityLevel::Medium);
+                let id = task.id;
+                pool.submit(task);
+                id
+            })
+            .collect()
+    }
+
+    #[test]
+    fn results_map_back_to_their_tasks() {
+        let handler = BatchIndex::default();
+        let batches = Arc::clone(&handler.batches);
+        let pool = BatchWorkerPool::new(handler).with_batch_size(3);
+        let ids = submit_tasks(&pool, 7);
+
+        let outcomes = pool.run();
+
+        assert_eq!(batches.load(Ordering::SeqCst), 3);
+        let task_ids: Vec<_> = outcomes.iter().map(|outcome| outcome.task_id).collect();
+        assert_eq!(task_ids, ids);
+        let results: Vec<_> = outcomes.into_iter().map(|outcome| outcome.result).collect();
+        assert_eq!(results, [Ok(0), Ok(1), Ok(2), Ok(0), Ok(1), Ok(2), Ok(0)]);
+    }
+
+    #[test]
+    fn a_panicking_batch_fails_each_of_its_tasks() {
+        let pool = BatchWorkerPool::new(Panics).with_batch_size(2);
+        submit_tasks(&pool, 3);
+
+        let outcomes = pool.run();
+
+        assert_eq!(outcomes.len(), 3);
+        let panic = Err(TaskError::HandlerPanic("batch panic".into()));
+        assert!(outcomes.iter().all(|outcome| outcome.result == panic));
+    }
+}
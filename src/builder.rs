@@ -0,0 +1,166 @@
+use std::time::SystemTime;
+
+use uuid::Uuid;
+
+use crate::timeout::Timeout;
+use crate::{PriorityLevel, Task, TaskError, TaskHandler};
+
+pub struct TaskBuilder {
+    handler: Box<dyn TaskHandler + Send + Sync>,
+    priority_level: PriorityLevel,
+    correlation_id: Option<Uuid>,
+    tenant_id: Option<String>,
+    deadline: Option<SystemTime>,
+}
+
+impl TaskBuilder {
+    pub fn new(handler: impl TaskHandler + Send + Sync + 'static) -> Self {
+        TaskBuilder {
+            handler: Box::new(handler),
+            priority_level: PriorityLevel::Low,
+            correlation_id: None,
+            tenant_id: None,
+            deadline: None,
+        }
+    }
+
+    pub fn priority_level(mut self, priority_level: PriorityLevel) -> Self {
+        self.priority_level = priority_level;
+        self
+    }
+
+    pub fn correlation_id(mut self, correlation_id: Uuid) -> Self {
+        self.correlation_id = Some(correlation_id);
+        self
+    }
+
+    pub fn tenant_id(mut self, tenant_id: impl Into<String>) -> Self {
+        self.tenant_id = Some(tenant_id.into());
+        self
+    }
+
+    /// The task is discarded if it is still queued at `deadline`, and times
+    /// out if it is still running then. The time left is worked out when the
+    /// task starts, so time spent waiting in the queue counts against it.
+    pub fn deadline(mut self, deadline: SystemTime) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Fails with [`TaskError::AlreadyExpired`] if the deadline has passed.
+    pub fn build(self) -> Result<Task, TaskError> {
+        let handler = match self.deadline {
+            Some(deadline) if deadline <= SystemTime::now() => {
+                return Err(TaskError::AlreadyExpired);
+            }
+            Some(deadline) => Box::new(Timeout::<dyn TaskHandler + Send + Sync>::until(
+                self.handler,
+                deadline,
+            )),
+            None => self.handler,
+        };
+
+        let mut task = Task::with_id(Uuid::new_v4(), handler, self.priority_level);
+        task.correlation_id = self.correlation_id;
+        task.tenant_id = self.tenant_id;
+        task.expires_at = self.deadline;
+        Ok(task)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::logger::WriterLogger;
+    use crate::worker::WorkerPool;
+
+    /// Sleeps, then records that it finished.
+    struct Slow {
+        sleep: Duration,
+        finished: Arc<AtomicBool>,
+    }
+
+    impl TaskHandler for Slow {
+        fn execute(&self) -> Result<i32, TaskError> {
+            thread::sleep(self.sleep);
+            self.finished.store(true, Ordering::SeqCst);
+            Ok(0)
+        }
+    }
+
+    fn slow(sleep: Duration) -> (Slow, Arc<AtomicBool>) {
+        let finished = Arc::new(AtomicBool::new(false));
+        let handler = Slow {
+            sleep,
+            finished: Arc::clone(&finished),
+        };
+        (handler, finished)
+    }
+
+    fn in_ms(ms: u64) -> SystemTime {
+        SystemTime::now() + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn build_copies_the_settings() {
+        let correlation_id = Uuid::new_v4();
+        let deadline = in_ms(60_000);
+        let (handler, _) = slow(Duration::ZERO);
+        let task = TaskBuilder::new(handler)
+            .priority_level(PriorityLevel::High)
+            .correlation_id(correlation_id)
+            .tenant_id("acme")
+            .deadline(deadline)
+            .build()
+            .unwrap();
+
+        assert_eq!(task.priority_level, PriorityLevel::High);
+        assert_eq!(task.correlation_id, Some(correlation_id));
+        assert_eq!(task.tenant_id.as_deref(), Some("acme"));
+        assert_eq!(task.expires_at, Some(deadline));
+        assert_eq!(task.handler.execute(), Ok(0));
+    }
+
+    #[test]
+    fn past_deadline_is_rejected() {
+        let (handler, _) = slow(Duration::ZERO);
+        let built = TaskBuilder::new(handler)
+            .deadline(SystemTime::now() - Duration::from_secs(1))
+            .build();
+        assert_eq!(built.err(), Some(TaskError::AlreadyExpired));
+    }
+
+    #[test]
+    fn task_still_queued_at_its_deadline_is_discarded() {
+        let (handler, finished) = slow(Duration::ZERO);
+        let task = TaskBuilder::new(handler)
+            .deadline(in_ms(30))
+            .build()
+            .unwrap();
+        let pool = WorkerPool::new(1).with_logger(Arc::new(WriterLogger::new(Vec::new())));
+        pool.submit(task);
+
+        thread::sleep(Duration::from_millis(60));
+        let outcome = pool.handle_one().unwrap();
+
+        assert_eq!(outcome.result, Err(TaskError::AlreadyExpired));
+        assert!(!finished.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn task_running_at_its_deadline_times_out() {
+        let (handler, finished) = slow(Duration::from_millis(300));
+        let task = TaskBuilder::new(handler)
+            .deadline(in_ms(50))
+            .build()
+            .unwrap();
+
+        assert_eq!(task.handler.execute(), Err(TaskError::Timeout));
+        assert!(!finished.load(Ordering::SeqCst));
+    }
+}
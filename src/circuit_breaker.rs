@@ -0,0 +1,254 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{catch_panics, TaskError, TaskHandler, TaskOutput};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    /// Set while the one call `HalfOpen` lets through is running.
+    trial_running: bool,
+}
+
+impl Breaker {
+    fn recover(&mut self, recovery_timeout: Duration) {
+        if self.state == CircuitState::Open
+            && self
+                .opened_at
+                .is_some_and(|opened_at| opened_at.elapsed() >= recovery_timeout)
+        {
+            self.state = CircuitState::HalfOpen;
+        }
+    }
+}
+
+/// Breaker state, shareable between handlers that should trip together.
+#[derive(Debug)]
+pub struct CircuitBreakerState {
+    breaker: Mutex<Breaker>,
+}
+
+impl Default for CircuitBreakerState {
+    fn default() -> Self {
+        CircuitBreakerState {
+            breaker: Mutex::new(Breaker {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                trial_running: false,
+            }),
+        }
+    }
+}
+
+impl CircuitBreakerState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn current(&self, recovery_timeout: Duration) -> CircuitState {
+        let mut breaker = self.breaker.lock().unwrap();
+        breaker.recover(recovery_timeout);
+        breaker.state
+    }
+
+    /// Whether a call may go through now. In `HalfOpen` only the first caller
+    /// may, as the trial; the rest are turned away until it finishes.
+    fn admit(&self, recovery_timeout: Duration) -> bool {
+        let mut breaker = self.breaker.lock().unwrap();
+        breaker.recover(recovery_timeout);
+        match breaker.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => !core::mem::replace(&mut breaker.trial_running, true),
+        }
+    }
+
+    /// Ends a trial that neither succeeded nor failed, so the next call is
+    /// let through instead.
+    fn record_skip(&self) {
+        self.breaker.lock().unwrap().trial_running = false;
+    }
+
+    fn record_success(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+        breaker.trial_running = false;
+        breaker.state = CircuitState::Closed;
+        breaker.consecutive_failures = 0;
+        breaker.opened_at = None;
+    }
+
+    fn record_failure(&self, failure_threshold: u32) {
+        let mut breaker = self.breaker.lock().unwrap();
+        breaker.trial_running = false;
+        breaker.consecutive_failures += 1;
+        if breaker.state == CircuitState::HalfOpen
+            || breaker.consecutive_failures >= failure_threshold
+        {
+            breaker.state = CircuitState::Open;
+            breaker.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+/// Stops calling `inner` after `failure_threshold` consecutive failures and
+/// fails fast with [`TaskError::CircuitOpen`] instead. Once
+/// `recovery_timeout` has passed, the next call is let through as a trial:
+/// success closes the circuit again, failure reopens it. Other calls made
+/// while the trial runs still fail fast.
+pub struct CircuitBreakerHandler<H: TaskHandler> {
+    inner: H,
+    state: Arc<CircuitBreakerState>,
+    failure_threshold: u32,
+    recovery_timeout: Duration,
+}
+
+impl<H: TaskHandler> CircuitBreakerHandler<H> {
+    pub fn new(inner: H, failure_threshold: u32, recovery_timeout: Duration) -> Self {
+        CircuitBreakerHandler {
+            inner,
+            state: Arc::new(CircuitBreakerState::new()),
+            failure_threshold: failure_threshold.max(1),
+            recovery_timeout,
+        }
+    }
+
+    pub fn with_state(mut self, state: Arc<CircuitBreakerState>) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn shared_state(&self) -> Arc<CircuitBreakerState> {
+        Arc::clone(&self.state)
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.state.current(self.recovery_timeout)
+    }
+}
+
+impl<H: TaskHandler> TaskHandler for CircuitBreakerHandler<H> {
+    fn execute(&self) -> Result<i32, TaskError> {
+        if !self.state.admit(self.recovery_timeout) {
+            return Err(TaskError::CircuitOpen);
+        }
+
+        let result = catch_panics(|| self.inner.execute());
+        match &result {
+            Ok(_) => self.state.record_success(),
+            Err(TaskError::Skipped) => self.state.record_skip(),
+            Err(_) => self.state.record_failure(self.failure_threshold),
+        }
+        result
+    }
+
+    fn handler_type_name(&self) -> &'static str {
+        self.inner.handler_type_name()
+    }
+
+    fn take_output(&self) -> Option<TaskOutput> {
+        self.inner.take_output()
+    }
+
+    fn cost_estimate(&self) -> Duration {
+        self.inner.cost_estimate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::*;
+
+    /// Fails while `failing` is set. Each call first waits for a message on
+    /// `gate`, if there is one.
+    struct Flaky {
+        failing: Mutex<bool>,
+        gate: Option<Mutex<mpsc::Receiver<()>>>,
+    }
+
+    impl Flaky {
+        fn failing() -> Self {
+            Flaky {
+                failing: Mutex::new(true),
+                gate: None,
+            }
+        }
+    }
+
+    impl TaskHandler for Flaky {
+        fn execute(&self) -> Result<i32, TaskError> {
+            if let Some(gate) = &self.gate {
+                gate.lock().unwrap().recv().unwrap();
+            }
+            if *self.failing.lock().unwrap() {
+                Err(TaskError::Failed("down".into()))
+            } else {
+                Ok(1)
+            }
+        }
+    }
+
+    const RECOVERY: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn opens_after_the_threshold_and_half_opens_after_recovery() {
+        let breaker = CircuitBreakerHandler::new(Flaky::failing(), 5, RECOVERY);
+        for _ in 0..4 {
+            assert_eq!(breaker.execute(), Err(TaskError::Failed("down".into())));
+            assert_eq!(breaker.state(), CircuitState::Closed);
+        }
+        assert_eq!(breaker.execute(), Err(TaskError::Failed("down".into())));
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert_eq!(breaker.execute(), Err(TaskError::CircuitOpen));
+
+        thread::sleep(RECOVERY);
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        // A failed trial reopens the circuit straight away.
+        assert_eq!(breaker.execute(), Err(TaskError::Failed("down".into())));
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        thread::sleep(RECOVERY);
+        *breaker.inner.failing.lock().unwrap() = false;
+        assert_eq!(breaker.execute(), Ok(1));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn half_open_admits_one_trial_at_a_time() {
+        let (release, gate) = mpsc::channel();
+        let inner = Flaky {
+            failing: Mutex::new(false),
+            gate: Some(Mutex::new(gate)),
+        };
+        let breaker = Arc::new(CircuitBreakerHandler::new(inner, 1, RECOVERY));
+        breaker.state.record_failure(1);
+        thread::sleep(RECOVERY);
+
+        let trial = {
+            let breaker = Arc::clone(&breaker);
+            thread::spawn(move || breaker.execute())
+        };
+        while !breaker.state.breaker.lock().unwrap().trial_running {
+            thread::yield_now();
+        }
+        assert_eq!(breaker.execute(), Err(TaskError::CircuitOpen));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        release.send(()).unwrap();
+        assert_eq!(trial.join().unwrap(), Ok(1));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
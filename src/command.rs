@@ -0,0 +1,104 @@
+use std::any::Any;
+use std::fmt::{self, Display};
+use std::process::Command;
+use std::sync::Mutex;
+
+use crate::{TaskError, TaskHandler};
+
+/// Runs a process and returns its exit code, zero or not. A process that
+/// cannot be spawned, or that is killed by a signal, fails the task.
+pub struct CommandTask(Mutex<Command>);
+
+impl CommandTask {
+    pub fn new(command: Command) -> Self {
+        CommandTask(Mutex::new(command))
+    }
+}
+
+impl From<Command> for CommandTask {
+    fn from(command: Command) -> Self {
+        CommandTask::new(command)
+    }
+}
+
+impl TaskHandler for CommandTask {
+    fn execute(&self) -> Result<i32, TaskError> {
+        let status = self.0.lock().unwrap().status();
+        let status = status
+            .map_err(|err| TaskError::Failed(format!("failed to spawn {}: {}", self, err)))?;
+        status
+            .code()
+            .ok_or_else(|| TaskError::Failed(format!("{} terminated by signal", self)))
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// Shows the program and its first argument, e.g. `echo hello`.
+impl Display for CommandTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let command = self.0.lock().unwrap();
+        write!(f, "{}", command.get_program().to_string_lossy())?;
+        if let Some(arg) = command.get_args().next() {
+            write!(f, " {}", arg.to_string_lossy())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{PriorityLevel, PriorityQueue, Task, TaskQueue};
+
+    fn shell(script: &str) -> CommandTask {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        CommandTask::new(command)
+    }
+
+    #[test]
+    fn exit_status_is_the_result() {
+        assert_eq!(CommandTask::new(Command::new("true")).execute(), Ok(0));
+        assert_eq!(CommandTask::new(Command::new("false")).execute(), Ok(1));
+        assert_eq!(shell("exit 42").execute(), Ok(42));
+    }
+
+    #[test]
+    fn runs_from_a_queue() {
+        let mut command = Command::new("echo");
+        command.arg("hello").stdout(std::process::Stdio::null());
+        let mut queue = PriorityQueue::new();
+        queue.push(Task::new(CommandTask::from(command), PriorityLevel::Medium));
+
+        let task = queue.pop_simple().unwrap();
+        assert_eq!(task.handler.execute(), Ok(0));
+    }
+
+    #[test]
+    fn missing_program_fails_to_spawn() {
+        let result = CommandTask::new(Command::new("/nonexistent/program")).execute();
+        assert!(
+            matches!(&result, Err(TaskError::Failed(message)) if message.starts_with("failed to spawn /nonexistent/program")),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn killed_process_fails_the_task() {
+        assert_eq!(
+            shell("kill -9 $$").execute(),
+            Err(TaskError::Failed("sh -c terminated by signal".into()))
+        );
+    }
+
+    #[test]
+    fn display_shows_the_program_and_first_argument() {
+        let mut command = Command::new("echo");
+        command.arg("hello").arg("world");
+        assert_eq!(CommandTask::new(command).to_string(), "echo hello");
+        assert_eq!(CommandTask::new(Command::new("true")).to_string(), "true");
+    }
+}
@@ -0,0 +1,372 @@
+//! One queue interface over several inner queues, e.g. one per task source.
+
+use uuid::Uuid;
+
+use crate::{PriorityQueue, QueueSnapshot, Summary, Task, TaskQueue};
+
+/// Which inner queue a [`CompositeQueue`] pops from next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    /// The queue whose next task has the most urgent priority level, the
+    /// first such queue on a tie. Sequence numbers are only compared within
+    /// a queue, as each queue numbers its own tasks.
+    Priority,
+    /// The non-empty queues in turn.
+    RoundRobin,
+    /// A non-empty queue picked at random, each equally likely.
+    Random,
+}
+
+/// Pops from several inner queues as if they were one. `push` goes to the
+/// first queue that has room, or to the last one if all are full, so a push
+/// never drops a task.
+pub struct CompositeQueue<Q: TaskQueue = PriorityQueue> {
+    queues: Vec<Q>,
+    capacities: Vec<usize>,
+    /// What each queue held after it was last popped, kept up to date by
+    /// `push`. `None` until then, or after the queue was changed directly.
+    snapshots: Vec<Option<QueueSnapshot>>,
+    strategy: SelectionStrategy,
+    /// The queue `RoundRobin` tries first, empty ones skipped. For `Random`,
+    /// a random number that picks among the non-empty queues.
+    cursor: usize,
+}
+
+impl<Q: TaskQueue> CompositeQueue<Q> {
+    /// Starts with `queue`, as there must always be one for `push` to go to.
+    /// See [`with_queue`](CompositeQueue::with_queue) for `capacity`.
+    pub fn new(strategy: SelectionStrategy, queue: Q, capacity: usize) -> Self {
+        CompositeQueue {
+            queues: vec![queue],
+            capacities: vec![capacity],
+            snapshots: vec![None],
+            strategy,
+            cursor: 0,
+        }
+    }
+
+    /// Adds `queue`, which takes pushed tasks while it holds fewer than
+    /// `capacity`. Tasks already in it are kept.
+    pub fn with_queue(mut self, queue: Q, capacity: usize) -> Self {
+        self.queues.push(queue);
+        self.capacities.push(capacity);
+        self.snapshots.push(None);
+        self
+    }
+
+    pub fn queues(&self) -> &[Q] {
+        &self.queues
+    }
+
+    /// Pushing through the returned queue bypasses the capacities.
+    pub fn queue_mut(&mut self, index: usize) -> Option<&mut Q> {
+        *self.snapshots.get_mut(index)? = None;
+        self.queues.get_mut(index)
+    }
+
+    pub fn into_queues(self) -> Vec<Q> {
+        self.queues
+    }
+
+    fn next_queue(&self) -> Option<usize> {
+        match self.strategy {
+            SelectionStrategy::Priority => self
+                .queues
+                .iter()
+                .enumerate()
+                .filter_map(|(index, queue)| Some((queue.peek()?.priority_level, index)))
+                .min()
+                .map(|(_, index)| index),
+            SelectionStrategy::RoundRobin => {
+                let count = self.queues.len();
+                (0..count)
+                    .map(|offset| (self.cursor + offset) % count)
+                    .find(|&index| !self.queues[index].is_empty())
+            }
+            SelectionStrategy::Random => {
+                let non_empty: Vec<usize> = (0..self.queues.len())
+                    .filter(|&index| !self.queues[index].is_empty())
+                    .collect();
+                non_empty.get(self.cursor % non_empty.len().max(1)).copied()
+            }
+        }
+    }
+
+    fn advance_cursor(&mut self, popped_from: usize) {
+        let count = self.queues.len();
+        self.cursor = match self.strategy {
+            SelectionStrategy::Priority => self.cursor,
+            SelectionStrategy::RoundRobin => (popped_from + 1) % count,
+            // A v4 uuid is 122 random bits, plenty for an index.
+            SelectionStrategy::Random => Uuid::new_v4().as_u128() as usize,
+        };
+    }
+
+    /// How many tasks are popped before the one at `position` in
+    /// `queues[index]`.
+    fn global_position(&self, index: usize, position: usize, task: &Task) -> usize {
+        match self.strategy {
+            // Every more urgent task goes first, and so do tasks of the same
+            // level in earlier queues.
+            SelectionStrategy::Priority => {
+                position
+                    + self
+                        .queues
+                        .iter()
+                        .enumerate()
+                        .filter(|&(other, _)| other != index)
+                        .map(|(other, queue)| {
+                            queue
+                                .find_all(|queued| {
+                                    (queued.priority_level, other) < (task.priority_level, index)
+                                })
+                                .len()
+                        })
+                        .sum::<usize>()
+            }
+            // Each round pops one task from every queue that still has one,
+            // starting at the cursor, so the task goes in round `position`.
+            SelectionStrategy::RoundRobin | SelectionStrategy::Random => {
+                let count = self.queues.len();
+                let turn = (index + count - self.cursor % count) % count;
+                position
+                    + (0..count)
+                        .filter(|&other| other != index)
+                        .map(|other| {
+                            let other_turn = (other + count - self.cursor % count) % count;
+                            let rounds = position + usize::from(other_turn < turn);
+                            self.queues[other].len().min(rounds)
+                        })
+                        .sum::<usize>()
+            }
+        }
+    }
+
+    fn snapshot(&mut self, index: usize) -> QueueSnapshot {
+        let queues = &self.queues;
+        self.snapshots[index]
+            .get_or_insert_with(|| {
+                let mut snapshot = QueueSnapshot::default();
+                for task in queues[index].find_all(|_| true) {
+                    snapshot.remaining += 1;
+                    *snapshot.count_mut(&task.priority_level) += 1;
+                }
+                snapshot
+            })
+            .clone()
+    }
+
+    fn matches_in_pop_order<F: Fn(&Task) -> bool>(&self, predicate: F) -> Vec<&Task> {
+        let mut matches: Vec<(usize, &Task)> = Vec::new();
+        for (index, queue) in self.queues.iter().enumerate() {
+            for task in queue.find_all(&predicate) {
+                if let Some(position) = queue.task_position(task.id) {
+                    matches.push((self.global_position(index, position, task), task));
+                }
+            }
+        }
+        matches.sort_by_key(|(position, _)| *position);
+        matches.into_iter().map(|(_, task)| task).collect()
+    }
+}
+
+impl<Q: TaskQueue> TaskQueue for CompositeQueue<Q> {
+    fn push(&mut self, task: Task) {
+        let index = (0..self.queues.len())
+            .find(|&index| self.queues[index].len() < self.capacities[index])
+            .unwrap_or(self.queues.len() - 1);
+        let id = task.id;
+        self.queues[index].push(task);
+        if let Some(snapshot) = &mut self.snapshots[index] {
+            // Read back, as the inner queue may have changed the priority.
+            match self.queues[index].find_by_id(id) {
+                Some(task) => {
+                    snapshot.remaining += 1;
+                    *snapshot.count_mut(&task.priority_level) += 1;
+                }
+                None => self.snapshots[index] = None,
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<(Task, QueueSnapshot)> {
+        let index = self.next_queue()?;
+        let (task, popped_from) = self.queues[index].pop()?;
+        self.snapshots[index] = Some(popped_from);
+        self.advance_cursor(index);
+        let mut snapshot = QueueSnapshot::default();
+        for other in 0..self.queues.len() {
+            let inner = self.snapshot(other);
+            snapshot.remaining += inner.remaining;
+            snapshot.high_count += inner.high_count;
+            snapshot.medium_count += inner.medium_count;
+            snapshot.low_count += inner.low_count;
+        }
+        Some((task, snapshot))
+    }
+
+    fn peek(&self) -> Option<&Task> {
+        self.queues[self.next_queue()?].peek()
+    }
+
+    fn len(&self) -> usize {
+        self.queues.iter().map(TaskQueue::len).sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queues.iter().all(TaskQueue::is_empty)
+    }
+
+    /// With `Random` this is the position if the queues were taken in turn
+    /// from here on, as the actual order is not known in advance.
+    fn task_position(&self, id: Uuid) -> Option<usize> {
+        self.queues.iter().enumerate().find_map(|(index, queue)| {
+            let position = queue.task_position(id)?;
+            let task = queue.find_by_id(id)?;
+            Some(self.global_position(index, position, task))
+        })
+    }
+
+    fn find<F: Fn(&Task) -> bool>(&self, predicate: F) -> Option<&Task> {
+        self.matches_in_pop_order(predicate).into_iter().next()
+    }
+
+    fn find_all<F: Fn(&Task) -> bool>(&self, predicate: F) -> Vec<&Task> {
+        self.matches_in_pop_order(predicate)
+    }
+
+    fn map_in_place<F: FnMut(&mut Task)>(&mut self, mut f: F) {
+        for queue in &mut self.queues {
+            queue.map_in_place(&mut f);
+        }
+        self.snapshots.fill(None);
+    }
+
+    /// Runs every task on the current thread, in the order the strategy
+    /// picks them.
+    fn handle(&mut self) -> Summary {
+        self.handle_blocking()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HardProblem, PriorityLevel};
+
+    fn task(priority_level: PriorityLevel) -> Task {
+        Task::new(HardProblem::new(1, 2), priority_level)
+    }
+
+    /// Pushes `n` tasks straight into queue `index` and returns their ids.
+    fn fill(queue: &mut CompositeQueue, index: usize, n: usize) -> Vec<Uuid> {
+        let inner = queue.queue_mut(index).unwrap();
+        (0..n)
+            .map(|_| {
+                let task = task(PriorityLevel::Medium);
+                let id = task.id;
+                inner.push(task);
+                id
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_robin_alternates_between_queues() {
+        let mut queue = CompositeQueue::new(SelectionStrategy::RoundRobin, PriorityQueue::new(), 2)
+            .with_queue(PriorityQueue::new(), 2);
+        let mut ids = Vec::new();
+        for _ in 0..4 {
+            let task = task(PriorityLevel::Medium);
+            ids.push(task.id);
+            queue.push(task);
+        }
+        assert_eq!(queue.queues()[0].len(), 2);
+        assert_eq!(queue.queues()[1].len(), 2);
+
+        let popped: Vec<Uuid> = core::iter::from_fn(|| queue.pop_simple())
+            .map(|task| task.id)
+            .collect();
+        assert_eq!(popped, [ids[0], ids[2], ids[1], ids[3]]);
+    }
+
+    #[test]
+    fn push_overflows_into_the_last_queue() {
+        let mut queue = CompositeQueue::new(SelectionStrategy::Priority, PriorityQueue::new(), 1)
+            .with_queue(PriorityQueue::new(), 1);
+        for _ in 0..3 {
+            queue.push(task(PriorityLevel::Low));
+        }
+        assert_eq!(queue.queues()[0].len(), 1);
+        assert_eq!(queue.queues()[1].len(), 2);
+    }
+
+    #[test]
+    fn priority_pops_the_most_urgent_across_queues() {
+        let mut queue = CompositeQueue::new(SelectionStrategy::Priority, PriorityQueue::new(), 2)
+            .with_queue(PriorityQueue::new(), 2);
+        queue.push(task(PriorityLevel::Low));
+        queue.push(task(PriorityLevel::Medium));
+        queue.push(task(PriorityLevel::High));
+
+        let (first, snapshot) = queue.pop().unwrap();
+        assert_eq!(first.priority_level, PriorityLevel::High);
+        assert_eq!(
+            snapshot,
+            QueueSnapshot {
+                remaining: 2,
+                high_count: 0,
+                medium_count: 1,
+                low_count: 1,
+            }
+        );
+
+        // Kept in step with pushes made after the snapshots were taken.
+        queue.push(task(PriorityLevel::High));
+        let (second, snapshot) = queue.pop().unwrap();
+        assert_eq!(second.priority_level, PriorityLevel::High);
+        assert_eq!((snapshot.remaining, snapshot.high_count), (2, 0));
+        let (_, snapshot) = queue.pop().unwrap();
+        assert_eq!((snapshot.remaining, snapshot.low_count), (1, 1));
+    }
+
+    #[test]
+    fn priority_ties_go_to_the_earlier_queue() {
+        let mut queue = CompositeQueue::new(SelectionStrategy::Priority, PriorityQueue::new(), 0)
+            .with_queue(PriorityQueue::new(), 0);
+        // Both queues number their tasks from zero, so sequence numbers
+        // cannot order tasks across them.
+        let second = fill(&mut queue, 1, 2);
+        let first = fill(&mut queue, 0, 2);
+        let urgent = task(PriorityLevel::High);
+        let urgent_id = urgent.id;
+        queue.queue_mut(1).unwrap().push(urgent);
+
+        let expected = [vec![urgent_id], first, second].concat();
+        for (position, id) in expected.iter().enumerate() {
+            assert_eq!(queue.task_position(*id), Some(position));
+        }
+        let popped: Vec<Uuid> = core::iter::from_fn(|| queue.pop_simple())
+            .map(|task| task.id)
+            .collect();
+        assert_eq!(popped, expected);
+    }
+
+    #[test]
+    fn random_picks_each_non_empty_queue_equally_often() {
+        let mut queue = CompositeQueue::new(SelectionStrategy::Random, PriorityQueue::new(), 0);
+        for _ in 0..3 {
+            queue = queue.with_queue(PriorityQueue::new(), 0);
+        }
+        // Queues 1 and 2 stay empty; picking the first non-empty queue after
+        // a random one would choose queue 3 three times as often as queue 0.
+        let first = fill(&mut queue, 0, 200);
+        fill(&mut queue, 3, 200);
+
+        let from_first = (0..200)
+            .filter(|_| first.contains(&queue.pop_simple().unwrap().id))
+            .count();
+
+        assert!((60..=140).contains(&from_first), "{} of 200", from_first);
+    }
+}
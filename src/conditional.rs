@@ -0,0 +1,108 @@
+use core::time::Duration;
+
+use crate::{TaskError, TaskHandler, TaskOutput};
+
+/// Runs `inner` only if `predicate` still holds when the task is executed,
+/// otherwise reports the task as [`TaskError::Skipped`].
+pub struct ConditionalTask<P, H>
+where
+    P: Fn() -> bool + Send + Sync,
+    H: TaskHandler + Send + Sync,
+{
+    predicate: P,
+    inner: H,
+}
+
+impl<P, H> ConditionalTask<P, H>
+where
+    P: Fn() -> bool + Send + Sync,
+    H: TaskHandler + Send + Sync,
+{
+    pub fn new(predicate: P, inner: H) -> Self {
+        ConditionalTask { predicate, inner }
+    }
+}
+
+impl<P, H> TaskHandler for ConditionalTask<P, H>
+where
+    P: Fn() -> bool + Send + Sync,
+    H: TaskHandler + Send + Sync,
+{
+    fn execute(&self) -> Result<i32, TaskError> {
+        if !(self.predicate)() {
+            return Err(TaskError::Skipped);
+        }
+        self.inner.execute()
+    }
+
+    fn handler_type_name(&self) -> &'static str {
+        self.inner.handler_type_name()
+    }
+
+    fn take_output(&self) -> Option<TaskOutput> {
+        self.inner.take_output()
+    }
+
+    fn cost_estimate(&self) -> Duration {
+        self.inner.cost_estimate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{PriorityLevel, PriorityQueue, Task, TaskQueue};
+
+    /// Counts its executions.
+    struct Counts(Arc<AtomicUsize>);
+
+    impl TaskHandler for Counts {
+        fn execute(&self) -> Result<i32, TaskError> {
+            Ok(self.0.fetch_add(1, Ordering::SeqCst) as i32)
+        }
+    }
+
+    #[test]
+    fn predicate_is_checked_at_execution_time() {
+        let enabled = Arc::new(AtomicBool::new(false));
+        let runs = Arc::new(AtomicUsize::new(0));
+        let task = ConditionalTask::new(
+            {
+                let enabled = Arc::clone(&enabled);
+                move || enabled.load(Ordering::SeqCst)
+            },
+            Counts(Arc::clone(&runs)),
+        );
+
+        assert_eq!(task.execute(), Err(TaskError::Skipped));
+        enabled.store(true, Ordering::SeqCst);
+        assert_eq!(task.execute(), Ok(0));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(task.handler_type_name(), Counts(runs).handler_type_name());
+    }
+
+    #[test]
+    fn skipped_tasks_are_counted_in_the_summary() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut queue = PriorityQueue::new();
+        for (index, condition) in [true, false, true, false, false].into_iter().enumerate() {
+            let handler = ConditionalTask::new(move || condition, Counts(Arc::clone(&runs)));
+            queue.push(Task::with_id(
+                Uuid::from_u128(index as u128),
+                Box::new(handler),
+                PriorityLevel::Medium,
+            ));
+        }
+
+        let summary = queue.handle_blocking();
+
+        assert_eq!((summary.executed, summary.skipped), (2, 3));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{Task, TaskError, TaskQueue};
+
+/// Tasks that failed, kept with the error they failed with so they can be
+/// inspected or retried later. Filled by
+/// [`ErrorStrategy::DeadLetter`](crate::ErrorStrategy::DeadLetter).
+#[derive(Default)]
+pub struct DeadLetterQueue {
+    entries: Vec<(Task, TaskError)>,
+}
+
+impl DeadLetterQueue {
+    pub fn new() -> Self {
+        DeadLetterQueue::default()
+    }
+
+    pub fn push(&mut self, task: Task, error: TaskError) {
+        self.entries.push((task, error));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Oldest failure first.
+    pub fn iter(&self) -> impl Iterator<Item = &(Task, TaskError)> {
+        self.entries.iter()
+    }
+
+    pub fn drain(&mut self) -> Vec<(Task, TaskError)> {
+        std::mem::take(&mut self.entries)
+    }
+
+    /// Pushes every dead task back onto `queue` and returns how many there
+    /// were.
+    pub fn retry_into(&mut self, queue: &mut dyn TaskQueue) -> usize {
+        let tasks = self.drain();
+        let count = tasks.len();
+        for (task, _) in tasks {
+            queue.push(task);
+        }
+        count
+    }
+}
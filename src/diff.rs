@@ -0,0 +1,133 @@
+//! Change detection between two points in a queue's life, for tests.
+//!
+//! [`QueueSnapshot`](crate::QueueSnapshot) only keeps counts, so diffs are
+//! taken over [`QueueContents`], which records every task id.
+
+use alloc::vec::Vec;
+
+use hashbrown::HashMap;
+use uuid::Uuid;
+
+use crate::{PriorityLevel, PriorityQueue};
+
+/// The id and priority of every queued task, in pop order.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QueueContents {
+    pub tasks: Vec<(Uuid, PriorityLevel)>,
+}
+
+impl PriorityQueue {
+    pub fn contents(&self) -> QueueContents {
+        QueueContents {
+            tasks: self
+                .iter()
+                .map(|task| (task.id, task.priority_level))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QueueDiff {
+    pub added: Vec<Uuid>,
+    pub removed: Vec<Uuid>,
+    /// `(id, before, after)` for tasks present in both.
+    pub priority_changed: Vec<(Uuid, PriorityLevel, PriorityLevel)>,
+}
+
+impl QueueDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.priority_changed.is_empty()
+    }
+}
+
+pub fn diff(before: &QueueContents, after: &QueueContents) -> QueueDiff {
+    let before_levels: HashMap<Uuid, PriorityLevel> = before.tasks.iter().copied().collect();
+    let after_levels: HashMap<Uuid, PriorityLevel> = after.tasks.iter().copied().collect();
+
+    let mut diff = QueueDiff::default();
+    for (id, level) in &after.tasks {
+        match before_levels.get(id) {
+            None => diff.added.push(*id),
+            Some(old) if old != level => diff.priority_changed.push((*id, *old, *level)),
+            Some(_) => {}
+        }
+    }
+    diff.removed = before
+        .tasks
+        .iter()
+        .filter(|(id, _)| !after_levels.contains_key(id))
+        .map(|(id, _)| *id)
+        .collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn contents(tasks: &[(u128, PriorityLevel)]) -> QueueContents {
+        QueueContents {
+            tasks: tasks
+                .iter()
+                .map(|&(id, level)| (Uuid::from_u128(id), level))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn reports_added_removed_and_changed_tasks() {
+        let before = contents(&[
+            (1, PriorityLevel::High),
+            (2, PriorityLevel::Low),
+            (3, PriorityLevel::Medium),
+        ]);
+        let after = contents(&[
+            (4, PriorityLevel::High),
+            (3, PriorityLevel::High),
+            (1, PriorityLevel::High),
+        ]);
+
+        assert_eq!(
+            diff(&before, &after),
+            QueueDiff {
+                added: vec![Uuid::from_u128(4)],
+                removed: vec![Uuid::from_u128(2)],
+                priority_changed: vec![(
+                    Uuid::from_u128(3),
+                    PriorityLevel::Medium,
+                    PriorityLevel::High
+                )],
+            }
+        );
+    }
+
+    #[test]
+    fn reordering_alone_is_no_change() {
+        let before = contents(&[(1, PriorityLevel::Medium), (2, PriorityLevel::Medium)]);
+        let after = contents(&[(2, PriorityLevel::Medium), (1, PriorityLevel::Medium)]);
+        assert!(diff(&before, &after).is_empty());
+        assert!(diff(&QueueContents::default(), &QueueContents::default()).is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn contents_list_the_queue_in_pop_order() {
+        use crate::{HardProblem, Task, TaskQueue};
+
+        let mut queue = PriorityQueue::new();
+        let low = Task::new(HardProblem::new(1, 2), PriorityLevel::Low);
+        let high = Task::new(HardProblem::new(1, 2), PriorityLevel::High);
+        let (low_id, high_id) = (low.id, high.id);
+        queue.push(low);
+        let before = queue.contents();
+        queue.push(high);
+
+        assert_eq!(
+            queue.contents().tasks,
+            [(high_id, PriorityLevel::High), (low_id, PriorityLevel::Low)]
+        );
+        assert_eq!(diff(&before, &queue.contents()).added, [high_id]);
+    }
+}
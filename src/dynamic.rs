@@ -0,0 +1,282 @@
+//! A queue that ages tasks, so low-priority work is not starved by a steady
+//! stream of urgent tasks.
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+
+use uuid::Uuid;
+
+use crate::logger::{Logger, WriterLogger};
+use crate::rate_limit::{Clock, SystemClock};
+use crate::{catch_panics, PriorityLevel, QueueSnapshot, Summary, Task, TaskQueue};
+
+const DEFAULT_AGE_WEIGHT: f64 = 0.1;
+
+fn base_score(priority_level: PriorityLevel) -> f64 {
+    match priority_level {
+        PriorityLevel::High => 2.0,
+        PriorityLevel::Medium => 1.0,
+        PriorityLevel::Low => 0.0,
+    }
+}
+
+struct Entry {
+    task: Task,
+    enqueued_at: Instant,
+}
+
+/// Pops the task with the highest effective priority: its base score (`High`
+/// 2, `Medium` 1, `Low` 0) plus the age weight times the seconds it has been
+/// queued. Scores are recomputed against the clock on every pop; ties go to
+/// the task that has waited longest.
+///
+/// With the default age weight of 0.1, a `Low` task overtakes a newly pushed
+/// `High` one after 20 seconds.
+pub struct DynamicPriorityQueue {
+    entries: Vec<Entry>,
+    age_weight: f64,
+    clock: Arc<dyn Clock>,
+    logger: Option<Arc<dyn Logger>>,
+}
+
+impl Default for DynamicPriorityQueue {
+    fn default() -> Self {
+        DynamicPriorityQueue {
+            entries: Vec::new(),
+            age_weight: DEFAULT_AGE_WEIGHT,
+            clock: Arc::new(SystemClock),
+            logger: None,
+        }
+    }
+}
+
+impl DynamicPriorityQueue {
+    pub fn new() -> Self {
+        DynamicPriorityQueue::default()
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Sends the lines `handle` logs to `logger` instead of stdout.
+    pub fn with_logger(mut self, logger: Arc<dyn Logger>) -> Self {
+        self.logger = Some(logger);
+        self
+    }
+
+    fn logger(&self) -> Arc<dyn Logger> {
+        self.logger
+            .clone()
+            .unwrap_or_else(|| Arc::new(WriterLogger::stdout()))
+    }
+
+    /// Score added per second a task has been queued. `0.0` makes this a
+    /// plain priority queue.
+    pub fn set_age_weight(&mut self, weight: f64) {
+        self.age_weight = weight;
+    }
+
+    fn score(&self, entry: &Entry, now: Instant) -> f64 {
+        let waited = now.saturating_duration_since(entry.enqueued_at);
+        base_score(entry.task.priority_level) + self.age_weight * waited.as_secs_f64()
+    }
+
+    /// `Greater` if `a` would be popped before `b`.
+    fn rank(&self, a: &Entry, b: &Entry, now: Instant) -> Ordering {
+        self.score(a, now)
+            .total_cmp(&self.score(b, now))
+            .then(b.enqueued_at.cmp(&a.enqueued_at))
+    }
+
+    fn outranks(&self, a: &Entry, b: &Entry, now: Instant) -> bool {
+        self.rank(a, b, now).is_gt()
+    }
+
+    fn next_index(&self) -> Option<usize> {
+        let now = self.clock.now();
+        (0..self.entries.len()).reduce(|best, index| {
+            if self.outranks(&self.entries[index], &self.entries[best], now) {
+                index
+            } else {
+                best
+            }
+        })
+    }
+
+    fn snapshot(&self) -> QueueSnapshot {
+        let mut snapshot = QueueSnapshot::default();
+        for entry in &self.entries {
+            snapshot.remaining += 1;
+            *snapshot.count_mut(&entry.task.priority_level) += 1;
+        }
+        snapshot
+    }
+}
+
+impl TaskQueue for DynamicPriorityQueue {
+    fn push(&mut self, task: Task) {
+        self.entries.push(Entry {
+            task,
+            enqueued_at: self.clock.now(),
+        });
+    }
+
+    fn pop(&mut self) -> Option<(Task, QueueSnapshot)> {
+        let index = self.next_index()?;
+        let entry = self.entries.swap_remove(index);
+        Some((entry.task, self.snapshot()))
+    }
+
+    fn peek(&self) -> Option<&Task> {
+        self.next_index().map(|index| &self.entries[index].task)
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Position as of now; it can change as tasks age.
+    fn task_position(&self, id: Uuid) -> Option<usize> {
+        let now = self.clock.now();
+        let target = self.entries.iter().find(|entry| entry.task.id == id)?;
+        Some(
+            self.entries
+                .iter()
+                .filter(|entry| self.outranks(entry, target, now))
+                .count(),
+        )
+    }
+
+    fn find<F: Fn(&Task) -> bool>(&self, predicate: F) -> Option<&Task> {
+        let now = self.clock.now();
+        self.entries
+            .iter()
+            .filter(|entry| predicate(&entry.task))
+            .max_by(|a, b| self.rank(a, b, now))
+            .map(|entry| &entry.task)
+    }
+
+    fn find_all<F: Fn(&Task) -> bool>(&self, predicate: F) -> Vec<&Task> {
+        let now = self.clock.now();
+        let mut matches: Vec<&Entry> = self
+            .entries
+            .iter()
+            .filter(|entry| predicate(&entry.task))
+            .collect();
+        matches.sort_by(|a, b| self.rank(b, a, now));
+        matches.into_iter().map(|entry| &entry.task).collect()
+    }
+
+    fn map_in_place<F: FnMut(&mut Task)>(&mut self, mut f: F) {
+        for entry in &mut self.entries {
+            f(&mut entry.task);
+        }
+    }
+
+    /// Runs every task on the current thread, in effective priority order.
+    fn handle(&mut self) -> Summary {
+        let mut summary = Summary::default();
+        let logger = self.logger();
+        while let Some(task) = self.pop_simple() {
+            let result = catch_panics(|| task.handler.execute());
+            logger.log(&format!(
+                "Task {} (correlation {}) with priority {:?} executed with result: {:?}",
+                task.id,
+                task.child_correlation_id(),
+                task.priority_level,
+                result
+            ));
+            summary.record(&result);
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::rate_limit::SimulatedClock;
+    use crate::{HardProblem, TaskError, TaskHandler};
+
+    fn task(priority_level: PriorityLevel) -> Task {
+        Task::new(
+            HardProblem::new(1, 2).with_delay(Duration::ZERO),
+            priority_level,
+        )
+    }
+
+    #[test]
+    fn low_task_overtakes_new_high_tasks_as_it_ages() {
+        let clock = Arc::new(SimulatedClock::new());
+        let mut queue = DynamicPriorityQueue::new().with_clock(clock.clone());
+        let low = task(PriorityLevel::Low);
+        let low_id = low.id;
+        queue.push(low);
+
+        // After 19s the low task scores 1.9, below a fresh high task's 2.0.
+        clock.advance(Duration::from_secs(19));
+        let early = task(PriorityLevel::High);
+        let early_id = early.id;
+        queue.push(early);
+        assert_eq!(queue.pop_simple().map(|task| task.id), Some(early_id));
+
+        // After 21s it scores 2.1 and goes first.
+        clock.advance(Duration::from_secs(2));
+        let late = task(PriorityLevel::High);
+        let late_id = late.id;
+        queue.push(late);
+        assert_eq!(queue.task_position(low_id), Some(0));
+        assert_eq!(queue.pop_simple().map(|task| task.id), Some(low_id));
+        assert_eq!(queue.pop_simple().map(|task| task.id), Some(late_id));
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn zero_age_weight_keeps_static_priorities() {
+        let clock = Arc::new(SimulatedClock::new());
+        let mut queue = DynamicPriorityQueue::new().with_clock(clock.clone());
+        queue.set_age_weight(0.0);
+        queue.push(task(PriorityLevel::Low));
+        clock.advance(Duration::from_secs(3600));
+        queue.push(task(PriorityLevel::High));
+
+        let (first, snapshot) = queue.pop().unwrap();
+        assert_eq!(first.priority_level, PriorityLevel::High);
+        assert_eq!((snapshot.remaining, snapshot.low_count), (1, 1));
+    }
+
+    #[test]
+    fn handle_logs_through_the_logger() {
+        struct Fails;
+
+        impl TaskHandler for Fails {
+            fn execute(&self) -> Result<i32, TaskError> {
+                Err(TaskError::Failed("nope".into()))
+            }
+        }
+
+        let logger = Arc::new(WriterLogger::new(Vec::new()));
+        let mut queue = DynamicPriorityQueue::new().with_logger(logger.clone());
+        let mut failing = Task::new(Fails, PriorityLevel::Medium);
+        let root = Uuid::new_v4();
+        failing.correlation_id = Some(root);
+        queue.push(failing);
+        queue.push(task(PriorityLevel::Low));
+
+        let summary = queue.handle();
+
+        assert_eq!((summary.executed, summary.failed), (1, 1));
+        let output = logger.contents();
+        assert_eq!(output.lines().count(), 2);
+        assert!(output.contains(&format!("(correlation {})", root)));
+    }
+}
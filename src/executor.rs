@@ -0,0 +1,404 @@
+use std::fmt;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::logger::{Logger, WriterLogger};
+use crate::timeout::Timeout;
+use crate::worker::PriorityTimeoutConfig;
+use crate::{catch_panics, Task, TaskError, TaskHandler, TaskOutput};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskContext {
+    pub task_id: Uuid,
+    pub correlation_id: Option<Uuid>,
+    pub worker_id: Option<usize>,
+}
+
+impl TaskContext {
+    pub fn new(task_id: Uuid) -> Self {
+        TaskContext {
+            task_id,
+            correlation_id: None,
+            worker_id: None,
+        }
+    }
+
+    pub fn for_task(task: &Task) -> Self {
+        TaskContext {
+            correlation_id: task.correlation_id,
+            ..TaskContext::new(task.id)
+        }
+    }
+
+    /// See [`Task::child_correlation_id`].
+    pub fn child_correlation_id(&self) -> Uuid {
+        self.correlation_id.unwrap_or(self.task_id)
+    }
+
+    pub fn with_worker(mut self, worker_id: usize) -> Self {
+        self.worker_id = Some(worker_id);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskOutcome {
+    pub task_id: Uuid,
+    pub result: Result<i32, TaskError>,
+    /// See [`TaskHandler::take_output`] and [`extract_result`].
+    pub output: Option<TaskOutput>,
+}
+
+/// ```
+/// use task_handler::executor::TaskOutcome;
+/// use task_handler::TaskError;
+/// use uuid::Uuid;
+///
+/// let task_id = Uuid::nil();
+/// let ok = TaskOutcome { task_id, result: Ok(42), output: None };
+/// assert_eq!(ok.to_string(), format!("Task[id={}]: Ok(42)", task_id));
+///
+/// let err = TaskOutcome { task_id, result: Err(TaskError::Timeout), output: None };
+/// assert_eq!(err.to_string(), format!("Task[id={}]: Err(task timed out)", task_id));
+/// ```
+impl fmt::Display for TaskOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.result {
+            Ok(value) => write!(f, "Task[id={}]: Ok({})", self.task_id, value),
+            Err(err) => write!(f, "Task[id={}]: Err({})", self.task_id, err),
+        }
+    }
+}
+
+/// The typed result a handler left in `outcome`, if it is an `O`.
+pub fn extract_result<O: 'static>(outcome: &TaskOutcome) -> Option<&O> {
+    outcome.output.as_ref()?.downcast_ref()
+}
+
+/// How long to wait before each retry. `attempt` counts the retries already
+/// made, so `next_delay(0)` is the wait before the first retry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackoffStrategy {
+    Constant(Duration),
+    Linear {
+        initial: Duration,
+        increment: Duration,
+    },
+    Exponential {
+        initial: Duration,
+        factor: f64,
+        max: Duration,
+    },
+    /// Exponential backoff, randomly scaled by up to `jitter_fraction` in
+    /// either direction so that retries from many workers spread out.
+    ExponentialWithJitter {
+        initial: Duration,
+        factor: f64,
+        max: Duration,
+        jitter_fraction: f64,
+    },
+}
+
+impl BackoffStrategy {
+    pub fn next_delay(&self, attempt: u32) -> Duration {
+        match *self {
+            BackoffStrategy::Constant(delay) => delay,
+            BackoffStrategy::Linear { initial, increment } => {
+                initial.saturating_add(increment.saturating_mul(attempt))
+            }
+            BackoffStrategy::Exponential {
+                initial,
+                factor,
+                max,
+            } => exponential_delay(initial, factor, max, attempt, 1.0),
+            BackoffStrategy::ExponentialWithJitter {
+                initial,
+                factor,
+                max,
+                jitter_fraction,
+            } => {
+                // A v4 uuid is 122 random bits; the top 53 make a uniform f64.
+                let random = (Uuid::new_v4().as_u128() >> 75) as f64 / (1u64 << 53) as f64;
+                let scale = 1.0 + jitter_fraction * (2.0 * random - 1.0);
+                exponential_delay(initial, factor, max, attempt, scale)
+            }
+        }
+    }
+}
+
+fn exponential_delay(
+    initial: Duration,
+    factor: f64,
+    max: Duration,
+    attempt: u32,
+    scale: f64,
+) -> Duration {
+    let exponent = i32::try_from(attempt).unwrap_or(i32::MAX);
+    let secs = initial.as_secs_f64() * factor.powi(exponent) * scale;
+    Duration::try_from_secs_f64(secs.min(max.as_secs_f64())).unwrap_or(max)
+}
+
+/// Re-runs a failed task up to `max_retries` more times on the same worker,
+/// sleeping for the backoff delay before each retry. Skipped and expired
+/// tasks are never retried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub backoff: BackoffStrategy,
+}
+
+type StartHook = Arc<dyn Fn(&TaskContext) + Send + Sync>;
+type FinishHook = Arc<dyn Fn(&TaskContext, &Result<i32, TaskError>) + Send + Sync>;
+
+/// Everything that decides how a popped task is run, shared by all workers.
+#[derive(Default, Clone)]
+pub struct ExecutionPolicy {
+    pub timeouts: Option<PriorityTimeoutConfig>,
+    pub retry: Option<RetryPolicy>,
+    pub on_start: Option<StartHook>,
+    pub on_finish: Option<FinishHook>,
+    /// Receives the retry log lines. `None` writes them to stdout.
+    pub logger: Option<Arc<dyn Logger>>,
+}
+
+impl ExecutionPolicy {
+    fn timeout_for(&self, task: &Task) -> Option<Duration> {
+        self.timeouts
+            .as_ref()
+            .map(|timeouts| timeouts.timeout_for(&task.priority_level))
+    }
+}
+
+/// Runs a single task under `policy`: calls `on_start`, executes the
+/// handler (behind a [`Timeout`] if the policy has one for the task's
+/// priority) and any retries the policy allows, then calls `on_finish` with
+/// the final result. Expired tasks are discarded without calling either
+/// hook.
+pub fn execute_task(task: Task, ctx: TaskContext, policy: &ExecutionPolicy) -> TaskOutcome {
+    if task.is_expired() {
+        return TaskOutcome {
+            task_id: ctx.task_id,
+            result: Err(TaskError::AlreadyExpired),
+            output: None,
+        };
+    }
+
+    if let Some(on_start) = &policy.on_start {
+        on_start(&ctx);
+    }
+
+    let timeout = policy.timeout_for(&task);
+    let handler: Arc<dyn TaskHandler + Send + Sync> = task.handler.into();
+    let run = || match timeout {
+        Some(duration) => {
+            Timeout::<dyn TaskHandler + Send + Sync>::new(Arc::clone(&handler), duration).execute()
+        }
+        None => catch_panics(|| handler.execute()),
+    };
+
+    let mut result = run();
+    if let Some(retry) = &policy.retry {
+        for attempt in 0..retry.max_retries {
+            let err = match &result {
+                Ok(_) | Err(TaskError::Skipped | TaskError::AlreadyExpired) => break,
+                Err(err) => err,
+            };
+            let delay = retry.backoff.next_delay(attempt);
+            let line = format!(
+                "Task {} (correlation {}) failed: {}; retrying in {:?}",
+                ctx.task_id,
+                ctx.child_correlation_id(),
+                err,
+                delay
+            );
+            match &policy.logger {
+                Some(logger) => logger.log(&line),
+                None => WriterLogger::stdout().log(&line),
+            }
+            thread::sleep(delay);
+            result = run();
+        }
+    }
+
+    if let Some(on_finish) = &policy.on_finish {
+        on_finish(&ctx, &result);
+    }
+
+    TaskOutcome {
+        task_id: ctx.task_id,
+        result,
+        output: handler.take_output(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::PriorityLevel;
+
+    type Events = Arc<Mutex<Vec<String>>>;
+
+    /// Records each call in `events` and fails the first `failures` of them.
+    struct Recording {
+        events: Events,
+        failures: Mutex<u32>,
+    }
+
+    impl TaskHandler for Recording {
+        fn execute(&self) -> Result<i32, TaskError> {
+            self.events.lock().unwrap().push("execute".into());
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(TaskError::Failed("not yet".into()));
+            }
+            Ok(3)
+        }
+    }
+
+    fn recording_task(events: &Events, failures: u32) -> Task {
+        let handler = Recording {
+            events: Arc::clone(events),
+            failures: Mutex::new(failures),
+        };
+        Task::new(handler, PriorityLevel::Medium)
+    }
+
+    fn recording_policy(events: &Events) -> ExecutionPolicy {
+        let on_start = Arc::clone(events);
+        let on_finish = Arc::clone(events);
+        ExecutionPolicy {
+            on_start: Some(Arc::new(move |_| {
+                on_start.lock().unwrap().push("start".into())
+            })),
+            on_finish: Some(Arc::new(move |_, result| {
+                on_finish
+                    .lock()
+                    .unwrap()
+                    .push(format!("finish {:?}", result))
+            })),
+            logger: Some(Arc::new(WriterLogger::new(Vec::new()))),
+            ..ExecutionPolicy::default()
+        }
+    }
+
+    #[test]
+    fn hooks_fire_around_the_handler() {
+        let events = Events::default();
+        let task = recording_task(&events, 0);
+        let ctx = TaskContext::for_task(&task);
+
+        let outcome = execute_task(task, ctx, &recording_policy(&events));
+
+        assert_eq!(outcome.result, Ok(3));
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["start", "execute", "finish Ok(3)"]
+        );
+    }
+
+    #[test]
+    fn retries_run_between_the_hooks() {
+        let events = Events::default();
+        let task = recording_task(&events, 2);
+        let ctx = TaskContext::for_task(&task);
+        let policy = ExecutionPolicy {
+            retry: Some(RetryPolicy {
+                max_retries: 2,
+                backoff: BackoffStrategy::Constant(Duration::ZERO),
+            }),
+            ..recording_policy(&events)
+        };
+
+        let outcome = execute_task(task, ctx, &policy);
+
+        assert_eq!(outcome.result, Ok(3));
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["start", "execute", "execute", "execute", "finish Ok(3)"]
+        );
+    }
+
+    #[test]
+    fn expired_tasks_skip_the_hooks() {
+        let events = Events::default();
+        let mut task = recording_task(&events, 0);
+        task.expires_at = Some(std::time::SystemTime::UNIX_EPOCH);
+        let ctx = TaskContext::for_task(&task);
+
+        let outcome = execute_task(task, ctx, &recording_policy(&events));
+
+        assert_eq!(outcome.result, Err(TaskError::AlreadyExpired));
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    fn delays(backoff: &BackoffStrategy, attempts: u32) -> Vec<Duration> {
+        (0..attempts)
+            .map(|attempt| backoff.next_delay(attempt))
+            .collect()
+    }
+
+    #[test]
+    fn constant_and_linear_delays() {
+        let ms = Duration::from_millis;
+        assert_eq!(
+            delays(&BackoffStrategy::Constant(ms(50)), 3),
+            [ms(50), ms(50), ms(50)]
+        );
+        let linear = BackoffStrategy::Linear {
+            initial: ms(100),
+            increment: ms(25),
+        };
+        assert_eq!(delays(&linear, 4), [ms(100), ms(125), ms(150), ms(175)]);
+        let huge = BackoffStrategy::Linear {
+            initial: Duration::MAX,
+            increment: ms(1),
+        };
+        assert_eq!(huge.next_delay(3), Duration::MAX);
+    }
+
+    #[test]
+    fn exponential_delays_grow_up_to_the_max() {
+        let ms = Duration::from_millis;
+        let exponential = BackoffStrategy::Exponential {
+            initial: ms(10),
+            factor: 2.0,
+            max: ms(100),
+        };
+        assert_eq!(
+            delays(&exponential, 6),
+            [ms(10), ms(20), ms(40), ms(80), ms(100), ms(100)]
+        );
+        assert_eq!(exponential.next_delay(u32::MAX), ms(100));
+    }
+
+    #[test]
+    fn jitter_stays_within_its_fraction() {
+        let ms = Duration::from_millis;
+        let jittered = BackoffStrategy::ExponentialWithJitter {
+            initial: ms(100),
+            factor: 2.0,
+            max: Duration::from_secs(10),
+            jitter_fraction: 0.25,
+        };
+        let second: Vec<Duration> = (0..200).map(|_| jittered.next_delay(1)).collect();
+        assert!(second
+            .iter()
+            .all(|delay| (ms(150)..=ms(250)).contains(delay)));
+        // Random, so the delays spread out rather than all being equal.
+        assert!(second.iter().any(|delay| *delay != second[0]));
+
+        let capped = BackoffStrategy::ExponentialWithJitter {
+            initial: ms(100),
+            factor: 2.0,
+            max: ms(100),
+            jitter_fraction: 0.5,
+        };
+        assert!((0..50).all(|_| capped.next_delay(5) <= ms(100)));
+    }
+}
@@ -0,0 +1,88 @@
+use std::any::Any;
+use std::sync::Mutex;
+
+use tokio::sync::oneshot::Receiver;
+
+use crate::{TaskError, TaskHandler};
+
+/// Wraps a computation that is already running elsewhere; executing the task
+/// blocks until its result arrives. The result can only be taken once, so a
+/// second execution fails.
+///
+/// Must not be executed from inside an async task.
+pub struct FutureTask {
+    rx: Mutex<Option<Receiver<i32>>>,
+}
+
+impl From<Receiver<i32>> for FutureTask {
+    fn from(rx: Receiver<i32>) -> Self {
+        FutureTask {
+            rx: Mutex::new(Some(rx)),
+        }
+    }
+}
+
+impl TaskHandler for FutureTask {
+    fn execute(&self) -> Result<i32, TaskError> {
+        let rx = self
+            .rx
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| TaskError::Failed("future result already taken".into()))?;
+        rx.blocking_recv()
+            .map_err(|_| TaskError::Failed("future was dropped without a result".into()))
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    #[test]
+    fn waits_for_the_result_sent_from_elsewhere() {
+        let (tx, rx) = oneshot::channel();
+        let task = FutureTask::from(rx);
+        let sender = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            tx.send(42).unwrap();
+        });
+
+        assert_eq!(task.execute(), Ok(42));
+        sender.join().unwrap();
+    }
+
+    #[test]
+    fn result_can_only_be_taken_once() {
+        let (tx, rx) = oneshot::channel();
+        tx.send(7).unwrap();
+        let task = FutureTask::from(rx);
+
+        assert_eq!(task.execute(), Ok(7));
+        assert_eq!(
+            task.execute(),
+            Err(TaskError::Failed("future result already taken".into()))
+        );
+    }
+
+    #[test]
+    fn dropped_sender_fails_the_task() {
+        let (tx, rx) = oneshot::channel::<i32>();
+        drop(tx);
+        assert_eq!(
+            FutureTask::from(rx).execute(),
+            Err(TaskError::Failed(
+                "future was dropped without a result".into()
+            ))
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::rate_limit::{Clock, SystemClock};
+
+/// Tracks the share of tasks that failed within a sliding `window`. A
+/// [`WorkerPool`](crate::worker::WorkerPool) given one demotes every task
+/// submitted while the failure rate is above `threshold_percent` to `Low`.
+pub struct FailureRateTracker {
+    window: Duration,
+    threshold_percent: f64,
+    recent_outcomes: VecDeque<(Instant, bool)>,
+    clock: Arc<dyn Clock>,
+}
+
+impl FailureRateTracker {
+    pub fn new(window: Duration, threshold_percent: f64) -> Self {
+        FailureRateTracker {
+            window,
+            threshold_percent,
+            recent_outcomes: VecDeque::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn record(&mut self, failed: bool) {
+        let now = self.clock.now();
+        while self
+            .recent_outcomes
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) >= self.window)
+        {
+            self.recent_outcomes.pop_front();
+        }
+        self.recent_outcomes.push_back((now, failed));
+    }
+
+    /// Percentage, from 0 to 100, of the outcomes in the last `window` that
+    /// were failures. `0.0` if there are none.
+    pub fn failure_rate(&self) -> f64 {
+        let now = self.clock.now();
+        let (total, failed) = self
+            .recent_outcomes
+            .iter()
+            .filter(|(at, _)| now.duration_since(*at) < self.window)
+            .fold((0usize, 0usize), |(total, failed), (_, f)| {
+                (total + 1, failed + *f as usize)
+            });
+        if total == 0 {
+            return 0.0;
+        }
+        failed as f64 * 100.0 / total as f64
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.failure_rate() > self.threshold_percent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logger::WriterLogger;
+    use crate::rate_limit::SimulatedClock;
+    use crate::worker::WorkerPool;
+    use crate::{PriorityLevel, Task, TaskError, TaskHandler};
+
+    struct Returns(Result<i32, TaskError>);
+
+    impl TaskHandler for Returns {
+        fn execute(&self) -> Result<i32, TaskError> {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn failure_rate_covers_only_the_window() {
+        let clock = Arc::new(SimulatedClock::new());
+        let mut tracker =
+            FailureRateTracker::new(Duration::from_secs(10), 50.0).with_clock(clock.clone());
+        assert_eq!(tracker.failure_rate(), 0.0);
+
+        for index in 0..10 {
+            tracker.record(index < 8);
+        }
+        assert_eq!(tracker.failure_rate(), 80.0);
+        assert!(tracker.is_degraded());
+
+        clock.advance(Duration::from_secs(10));
+        tracker.record(false);
+        assert_eq!(tracker.failure_rate(), 0.0);
+        assert!(!tracker.is_degraded());
+    }
+
+    #[test]
+    fn pool_demotes_tasks_submitted_while_degraded() {
+        let tracker = FailureRateTracker::new(Duration::from_secs(60), 50.0);
+        let pool = WorkerPool::new(2)
+            .with_failure_rate_tracker(tracker)
+            .with_logger(Arc::new(WriterLogger::new(Vec::new())));
+        for index in 0..10 {
+            let result = if index < 8 {
+                Err(TaskError::Failed("overloaded".into()))
+            } else {
+                Ok(0)
+            };
+            pool.submit(Task::new(Returns(result), PriorityLevel::High));
+        }
+        let summary = pool.run();
+        assert_eq!((summary.executed, summary.failed), (2, 8));
+
+        let eleventh = Task::new(Returns(Ok(0)), PriorityLevel::High);
+        let id = eleventh.id;
+        pool.submit(eleventh);
+
+        let queue = pool.queue();
+        assert_eq!(
+            queue.lock().unwrap().priority_of(id),
+            Some(PriorityLevel::Low)
+        );
+    }
+}
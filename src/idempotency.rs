@@ -0,0 +1,165 @@
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+/// Ids of completed tasks, persisted one per line in an append-only file so
+/// that a restarted process does not run them a second time.
+pub struct PersistentIdSet {
+    path: PathBuf,
+    ids: HashSet<Uuid>,
+}
+
+impl PersistentIdSet {
+    /// Opens the set stored at `path`, creating an empty one if the file does
+    /// not exist yet. Blank lines are ignored. A partial last line, left by a
+    /// crash in the middle of [`mark_done`](PersistentIdSet::mark_done), is
+    /// cut off: that call never returned, so its task was not promised to be
+    /// recorded.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut ids = HashSet::new();
+        match fs::read(&path) {
+            Ok(contents) => {
+                let complete = contents
+                    .iter()
+                    .rposition(|&byte| byte == b'\n')
+                    .map_or(0, |newline| newline + 1);
+                if complete < contents.len() {
+                    OpenOptions::new()
+                        .write(true)
+                        .open(&path)?
+                        .set_len(complete as u64)?;
+                }
+                let contents = std::str::from_utf8(&contents[..complete])
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                for line in contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                {
+                    let id = Uuid::parse_str(line)
+                        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                    ids.insert(id);
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        Ok(PersistentIdSet { path, ids })
+    }
+
+    /// Records `id` and flushes it to disk before returning, so the record
+    /// survives an OS crash too.
+    pub fn mark_done(&mut self, id: Uuid) -> io::Result<()> {
+        if self.ids.contains(&id) {
+            return Ok(());
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", id)?;
+        file.sync_data()?;
+        self.ids.insert(id);
+        Ok(())
+    }
+
+    pub fn is_done(&self, id: Uuid) -> bool {
+        self.ids.contains(&id)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::logger::WriterLogger;
+    use crate::worker::WorkerPool;
+    use crate::{PriorityLevel, Task, TaskError, TaskHandler};
+
+    /// Removes the file when the test ends, pass or fail.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new() -> Self {
+            TempPath(std::env::temp_dir().join(format!("task-handler-{}.ids", Uuid::new_v4())))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    struct Counts(Arc<AtomicUsize>);
+
+    impl TaskHandler for Counts {
+        fn execute(&self) -> Result<i32, TaskError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(0)
+        }
+    }
+
+    /// Runs `ids` through a fresh pool backed by the set at `path`, as a
+    /// process starting up would.
+    fn run_process(path: &Path, ids: &[Uuid], runs: &Arc<AtomicUsize>) -> crate::Summary {
+        let store = Arc::new(Mutex::new(PersistentIdSet::open(path).unwrap()));
+        let pool = WorkerPool::new(2)
+            .with_idempotency_store(store)
+            .with_logger(Arc::new(WriterLogger::new(Vec::new())));
+        for &id in ids {
+            let handler = Box::new(Counts(Arc::clone(runs)));
+            pool.submit(Task::with_id(id, handler, PriorityLevel::Medium));
+        }
+        pool.run()
+    }
+
+    #[test]
+    fn restarted_pool_skips_tasks_done_before_the_crash() {
+        let path = TempPath::new();
+        let ids: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        // The first process gets through half of the tasks, then dies.
+        assert_eq!(run_process(&path.0, &ids[..2], &runs).executed, 2);
+
+        let summary = run_process(&path.0, &ids, &runs);
+
+        assert_eq!(summary.skipped, 2);
+        assert_eq!(summary.executed, 2);
+        assert_eq!(runs.load(Ordering::SeqCst), 4);
+        let reopened = PersistentIdSet::open(&path.0).unwrap();
+        assert!(ids.iter().all(|&id| reopened.is_done(id)));
+    }
+
+    #[test]
+    fn open_drops_a_torn_last_line() {
+        let path = TempPath::new();
+        let done = Uuid::new_v4();
+        let torn = Uuid::new_v4().to_string();
+        fs::write(&path.0, format!("{}\n\n{}", done, &torn[..10])).unwrap();
+
+        let mut set = PersistentIdSet::open(&path.0).unwrap();
+        assert!(set.is_done(done));
+        let next = Uuid::new_v4();
+        set.mark_done(next).unwrap();
+
+        let reopened = PersistentIdSet::open(&path.0).unwrap();
+        assert!(reopened.is_done(done));
+        assert!(reopened.is_done(next));
+        assert_eq!(
+            fs::read_to_string(&path.0).unwrap(),
+            format!("{}\n\n{}\n", done, next)
+        );
+    }
+}
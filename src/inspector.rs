@@ -0,0 +1,199 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use uuid::Uuid;
+
+use crate::{catch_panics, QueueSnapshot, Summary, Task, TaskError, TaskQueue};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditEventKind {
+    Push,
+    Pop,
+    Execute,
+    Complete(i32),
+    Fail(String),
+    Cancel,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEvent {
+    pub timestamp: Instant,
+    pub kind: AuditEventKind,
+    pub task_id: Option<Uuid>,
+}
+
+/// Wraps a queue and records every push, pop and execution in an audit log.
+/// Read-only calls such as `peek` and `len` are not logged.
+pub struct TaskQueueInspector<Q: TaskQueue> {
+    inner: Q,
+    log: Arc<Mutex<Vec<AuditEvent>>>,
+}
+
+impl<Q: TaskQueue> TaskQueueInspector<Q> {
+    pub fn new(inner: Q) -> Self {
+        TaskQueueInspector {
+            inner,
+            log: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn audit_log(&self) -> Vec<AuditEvent> {
+        self.log.lock().unwrap().clone()
+    }
+
+    pub fn into_inner(self) -> Q {
+        self.inner
+    }
+
+    fn record(&self, kind: AuditEventKind, task_id: Uuid) {
+        self.log.lock().unwrap().push(AuditEvent {
+            timestamp: Instant::now(),
+            kind,
+            task_id: Some(task_id),
+        });
+    }
+}
+
+impl<Q: TaskQueue> TaskQueue for TaskQueueInspector<Q> {
+    fn push(&mut self, task: Task) {
+        self.record(AuditEventKind::Push, task.id);
+        self.inner.push(task);
+    }
+
+    fn pop(&mut self) -> Option<(Task, QueueSnapshot)> {
+        let (task, snapshot) = self.inner.pop()?;
+        self.record(AuditEventKind::Pop, task.id);
+        Some((task, snapshot))
+    }
+
+    fn peek(&self) -> Option<&Task> {
+        self.inner.peek()
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn len_estimate(&self) -> usize {
+        self.inner.len_estimate()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    fn task_position(&self, id: Uuid) -> Option<usize> {
+        self.inner.task_position(id)
+    }
+
+    fn find<F: Fn(&Task) -> bool>(&self, predicate: F) -> Option<&Task> {
+        self.inner.find(predicate)
+    }
+
+    fn find_all<F: Fn(&Task) -> bool>(&self, predicate: F) -> Vec<&Task> {
+        self.inner.find_all(predicate)
+    }
+
+    fn find_by_id(&self, id: Uuid) -> Option<&Task> {
+        self.inner.find_by_id(id)
+    }
+
+    fn map_in_place<F: FnMut(&mut Task)>(&mut self, f: F) {
+        self.inner.map_in_place(f);
+    }
+
+    /// Runs the tasks one at a time on the calling thread so that each
+    /// result lands in the log right after its `Execute` event.
+    fn handle(&mut self) -> Summary {
+        let mut summary = Summary::default();
+        while let Some(task) = self.pop_simple() {
+            self.record(AuditEventKind::Execute, task.id);
+            let result = catch_panics(|| task.handler.execute());
+            let kind = match &result {
+                Ok(value) => AuditEventKind::Complete(*value),
+                Err(TaskError::Skipped | TaskError::AlreadyExpired) => AuditEventKind::Cancel,
+                Err(err) => AuditEventKind::Fail(err.to_string()),
+            };
+            self.record(kind, task.id);
+            summary.record(&result);
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::*;
+    use crate::{PriorityLevel, PriorityQueue, TaskHandler};
+
+    struct Returns(Result<i32, TaskError>);
+
+    impl TaskHandler for Returns {
+        fn execute(&self) -> Result<i32, TaskError> {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn audit_log_replays_the_run() {
+        let mut inspector = TaskQueueInspector::new(PriorityQueue::new());
+        let scenario = [
+            (Ok(5), PriorityLevel::High),
+            (
+                Err(TaskError::Failed("bad input".into())),
+                PriorityLevel::Medium,
+            ),
+            (Err(TaskError::Skipped), PriorityLevel::Low),
+        ];
+        let mut ids = Vec::new();
+        for (result, priority_level) in scenario {
+            let task = Task::new(Returns(result), priority_level);
+            ids.push(task.id);
+            inspector.push(task);
+        }
+        let summary = inspector.handle();
+
+        // Rebuild the queue contents and each task's outcome from the log.
+        let log = inspector.audit_log();
+        let mut queued = HashSet::new();
+        let mut outcomes = HashMap::new();
+        let mut replayed = Summary::default();
+        for event in &log {
+            let id = event.task_id.unwrap();
+            match &event.kind {
+                AuditEventKind::Push => assert!(queued.insert(id)),
+                AuditEventKind::Pop => assert!(queued.remove(&id)),
+                AuditEventKind::Execute => assert!(!queued.contains(&id)),
+                kind => {
+                    replayed.record(&match kind {
+                        AuditEventKind::Complete(value) => Ok(*value),
+                        AuditEventKind::Fail(message) => Err(TaskError::Failed(message.clone())),
+                        _ => Err(TaskError::Skipped),
+                    });
+                    outcomes.insert(id, kind.clone());
+                }
+            }
+        }
+
+        assert_eq!(log.len(), 12);
+        assert!(log
+            .windows(2)
+            .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+        assert!(queued.is_empty());
+        assert_eq!(replayed, summary);
+        assert_eq!(outcomes[&ids[0]], AuditEventKind::Complete(5));
+        assert_eq!(
+            outcomes[&ids[1]],
+            AuditEventKind::Fail("task failed: bad input".into())
+        );
+        assert_eq!(outcomes[&ids[2]], AuditEventKind::Cancel);
+        let executed: Vec<Uuid> = log
+            .iter()
+            .filter(|event| event.kind == AuditEventKind::Execute)
+            .map(|event| event.task_id.unwrap())
+            .collect();
+        assert_eq!(executed, ids);
+    }
+}
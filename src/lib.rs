@@ -0,0 +1,2829 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+compile_error!("task_handler requires either the `std` or the `alloc` feature");
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod circuit_breaker;
+#[cfg(feature = "std")]
+pub mod command;
+#[cfg(feature = "std")]
+pub mod composite;
+pub mod conditional;
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod dead_letter;
+#[cfg(feature = "std")]
+pub mod dynamic;
+#[cfg(feature = "std")]
+pub mod executor;
+#[cfg(feature = "tokio")]
+pub mod future_task;
+#[cfg(feature = "std")]
+pub mod health;
+#[cfg(feature = "std")]
+pub mod idempotency;
+#[cfg(feature = "std")]
+pub mod inspector;
+pub mod local;
+#[cfg(feature = "std")]
+pub mod logger;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod pipe;
+#[cfg(feature = "std")]
+pub mod rate_limit;
+#[cfg(feature = "std")]
+pub mod ready_set;
+#[cfg(feature = "plugins")]
+pub mod registry;
+#[cfg(feature = "std")]
+pub mod sandbox;
+#[cfg(feature = "serde")]
+mod serde_impls;
+#[cfg(feature = "std")]
+pub mod sharded_queue;
+#[cfg(feature = "signals")]
+pub mod signal;
+#[cfg(feature = "std")]
+pub mod sticky;
+#[cfg(feature = "tokio")]
+pub mod stream;
+#[cfg(feature = "std")]
+pub mod tenant;
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(feature = "std")]
+pub mod timeout;
+#[cfg(feature = "std")]
+pub mod typed;
+pub mod vec_queue;
+#[cfg(feature = "std")]
+pub mod watermark;
+#[cfg(feature = "std")]
+pub mod worker;
+
+use alloc::boxed::Box;
+use alloc::collections::BinaryHeap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::cmp::Reverse;
+use core::fmt::{self, Debug, Display};
+use core::ops::{Add, AddAssign, Div, Index, IndexMut, Mul, Sub, SubAssign};
+use core::str::FromStr;
+use core::time::Duration;
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
+use std::sync::mpsc::{Receiver, Sender};
+#[cfg(feature = "std")]
+use std::thread::{sleep, spawn, JoinHandle};
+use uuid::Uuid;
+
+#[cfg(feature = "macros")]
+pub use task_handler_macros::task;
+
+/// A plain `Copy` value: copies compare equal to the original. Ordering
+/// follows urgency, so `High < Medium < Low`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum PriorityLevel {
+    High,
+    Medium,
+    Low,
+}
+
+impl PriorityLevel {
+    fn more_urgent(self) -> Option<PriorityLevel> {
+        match self {
+            PriorityLevel::High => None,
+            PriorityLevel::Medium => Some(PriorityLevel::High),
+            PriorityLevel::Low => Some(PriorityLevel::Medium),
+        }
+    }
+
+    fn less_urgent(self) -> Option<PriorityLevel> {
+        match self {
+            PriorityLevel::High => Some(PriorityLevel::Medium),
+            PriorityLevel::Medium => Some(PriorityLevel::Low),
+            PriorityLevel::Low => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PriorityLevel::High => "high",
+            PriorityLevel::Medium => "medium",
+            PriorityLevel::Low => "low",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePriorityError(String);
+
+impl Display for ParsePriorityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown priority level: {:?}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParsePriorityError {}
+
+/// Accepts the level names in any case, or `1`/`2`/`3` for high/medium/low.
+impl FromStr for PriorityLevel {
+    type Err = ParsePriorityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let level = match s {
+            "1" => PriorityLevel::High,
+            "2" => PriorityLevel::Medium,
+            "3" => PriorityLevel::Low,
+            _ if s.eq_ignore_ascii_case("high") => PriorityLevel::High,
+            _ if s.eq_ignore_ascii_case("medium") => PriorityLevel::Medium,
+            _ if s.eq_ignore_ascii_case("low") => PriorityLevel::Low,
+            _ => return Err(ParsePriorityError(s.into())),
+        };
+        Ok(level)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskError {
+    Failed(String),
+    Skipped,
+    Timeout,
+    HandlerPanic(String),
+    AlreadyExpired,
+    CircuitOpen,
+}
+
+impl Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskError::Failed(message) => write!(f, "task failed: {}", message),
+            TaskError::Skipped => write!(f, "task skipped"),
+            TaskError::Timeout => write!(f, "task timed out"),
+            TaskError::HandlerPanic(message) => write!(f, "handler panicked: {}", message),
+            TaskError::AlreadyExpired => write!(f, "task expired before it ran"),
+            TaskError::CircuitOpen => write!(f, "circuit breaker is open"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TaskError {}
+
+pub trait TaskHandler {
+    fn execute(&self) -> Result<i32, TaskError>;
+
+    /// Names the handler in logs. Defaults to the full type name.
+    fn handler_type_name(&self) -> &'static str {
+        core::any::type_name::<Self>()
+    }
+
+    /// Hands over a result that does not fit in the `i32`, if the last
+    /// execution produced one. Called once after each execution.
+    fn take_output(&self) -> Option<TaskOutput> {
+        None
+    }
+
+    /// Roughly how long `execute` takes, used to skip tasks that would not
+    /// finish before they expire. Defaults to zero.
+    fn cost_estimate(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    /// The handler itself, for [`PriorityQueue::inspect_handler`] to downcast.
+    /// Defaults to `None`, which keeps the handler opaque.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+}
+
+/// Lets one handler back several tasks without cloning it.
+impl<T: TaskHandler + ?Sized> TaskHandler for Arc<T> {
+    fn execute(&self) -> Result<i32, TaskError> {
+        (**self).execute()
+    }
+
+    fn handler_type_name(&self) -> &'static str {
+        (**self).handler_type_name()
+    }
+
+    fn take_output(&self) -> Option<TaskOutput> {
+        (**self).take_output()
+    }
+
+    fn cost_estimate(&self) -> Duration {
+        (**self).cost_estimate()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        (**self).as_any()
+    }
+}
+
+/// A typed result, see [`TaskHandler::take_output`].
+pub type TaskOutput = Arc<dyn Any + Send + Sync>;
+
+/// Runs `execute`, turning a panic into [`TaskError::HandlerPanic`] so it
+/// cannot unwind through a worker. Without `std` panics cannot be caught and
+/// `execute` is called directly.
+pub fn catch_panics(execute: impl FnOnce() -> Result<i32, TaskError>) -> Result<i32, TaskError> {
+    #[cfg(feature = "std")]
+    {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(execute)).unwrap_or_else(|payload| {
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                String::from(*message)
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.clone()
+            } else {
+                String::from("unknown panic payload")
+            };
+            Err(TaskError::HandlerPanic(message))
+        })
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        execute()
+    }
+}
+
+/// Tally of handled tasks by outcome.
+///
+/// Ignoring a returned summary is a warning:
+///
+/// ```compile_fail
+/// #![deny(unused_must_use)]
+/// use task_handler::{PriorityQueue, TaskQueue};
+///
+/// let mut queue = PriorityQueue::new();
+/// queue.handle_blocking();
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[must_use = "this Summary records task outcomes; ignoring it discards failure information"]
+pub struct Summary {
+    pub executed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub panicked: usize,
+}
+
+impl Summary {
+    pub fn record(&mut self, result: &Result<i32, TaskError>) {
+        match result {
+            Ok(_) => self.executed += 1,
+            Err(TaskError::Skipped | TaskError::AlreadyExpired) => self.skipped += 1,
+            Err(TaskError::HandlerPanic(_)) => self.panicked += 1,
+            Err(_) => self.failed += 1,
+        }
+    }
+
+    pub fn merge(&mut self, other: &Summary) {
+        self.executed += other.executed;
+        self.failed += other.failed;
+        self.skipped += other.skipped;
+        self.panicked += other.panicked;
+    }
+}
+
+/// One line for CLI output:
+///
+/// ```
+/// use task_handler::Summary;
+///
+/// let summary = Summary { executed: 3, failed: 1, skipped: 0, panicked: 2 };
+/// assert_eq!(summary.to_string(), "Executed: 3, Failed: 1, Skipped: 0, Panicked: 2");
+/// ```
+impl Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Executed: {}, Failed: {}, Skipped: {}, Panicked: {}",
+            self.executed, self.failed, self.skipped, self.panicked
+        )
+    }
+}
+
+/// Returned by [`TaskQueue::handle_until_empty_or_error`] when a task fails:
+/// the error and the tally of the tasks handled before it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HaltedOnError {
+    pub error: TaskError,
+    pub summary: Summary,
+}
+
+/// What [`PriorityQueue::handle_with_error_strategy`] does with a task that
+/// fails or panics.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub enum ErrorStrategy {
+    /// Stop handling, leaving the remaining tasks in the queue.
+    FailFast,
+    /// Log the failure and carry on with the next task.
+    SkipAndContinue,
+    /// Push the task back once; only its second failure is counted.
+    RequeueOnce,
+    /// Move the task to the dead letter queue and carry on.
+    DeadLetter(Arc<std::sync::Mutex<dead_letter::DeadLetterQueue>>),
+}
+
+/// A queue being handled on a background thread, returned by
+/// [`PriorityQueue::handle_detached`].
+#[cfg(feature = "std")]
+pub struct DetachedHandle {
+    join: JoinHandle<Summary>,
+}
+
+#[cfg(feature = "std")]
+impl DetachedHandle {
+    /// Waits for every task to finish.
+    pub fn join(self) -> Summary {
+        self.join
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.join.is_finished()
+    }
+}
+
+/// Built with [`Task::new`] or [`Task::with_id`], then adjusted through its
+/// fields. New fields may be added without a breaking release, so the
+/// struct cannot be built literally outside this crate.
+#[non_exhaustive]
+pub struct Task {
+    pub id: Uuid,
+    pub handler: Box<dyn TaskHandler + Send + Sync>,
+    pub priority_level: PriorityLevel,
+    pub correlation_id: Option<Uuid>,
+    pub tags: Vec<String>,
+    /// Breaks ties between tasks of the same priority: lower numbers were
+    /// pushed earlier and run first. Assigned by [`PriorityQueue`] on push,
+    /// overwriting whatever the task was built with.
+    pub sequence_number: u64,
+    /// The [`WorkerPool`](worker::WorkerPool) worker that should run this
+    /// task, e.g. because it has the task's data cached.
+    pub preferred_worker: Option<usize>,
+    /// Tasks of one tenant share the limits a
+    /// [`WorkerPool`](worker::WorkerPool) sets for it with
+    /// [`TenantConfig`](tenant::TenantConfig).
+    pub tenant_id: Option<String>,
+    /// Past this point the task is discarded instead of run.
+    #[cfg(feature = "std")]
+    pub expires_at: Option<std::time::SystemTime>,
+    /// When the task was pushed, or last promoted for its age. Set by
+    /// [`PriorityQueue`] like `sequence_number`.
+    #[cfg(feature = "std")]
+    pub enqueued_at: Option<std::time::Instant>,
+}
+
+impl Task {
+    #[cfg(feature = "std")]
+    pub fn new(handler: impl TaskHandler + Send + Sync + 'static, priority_level: PriorityLevel) -> Self {
+        Task::with_id(Uuid::new_v4(), Box::new(handler), priority_level)
+    }
+
+    /// A task with the given id and every optional field unset. Unlike
+    /// [`Task::new`] this does not need `std` to generate the id.
+    pub fn with_id(id: Uuid, handler: Box<dyn TaskHandler + Send + Sync>, priority_level: PriorityLevel) -> Self {
+        Task {
+            id,
+            handler,
+            priority_level,
+            correlation_id: None,
+            tags: Vec::new(),
+            sequence_number: 0,
+            preferred_worker: None,
+            tenant_id: None,
+            #[cfg(feature = "std")]
+            expires_at: None,
+            #[cfg(feature = "std")]
+            enqueued_at: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= std::time::SystemTime::now())
+    }
+
+    /// The correlation id for tasks spawned from this one: its own
+    /// correlation id, or its id if it started the chain.
+    pub fn child_correlation_id(&self) -> Uuid {
+        self.correlation_id.unwrap_or(self.id)
+    }
+
+    pub fn priority_ref(&self) -> &PriorityLevel {
+        &self.priority_level
+    }
+}
+
+/// Handlers that know their own priority, such as those generated by the
+/// `#[task]` attribute.
+#[cfg(feature = "std")]
+pub trait IntoTask {
+    fn into_task(self) -> Task;
+}
+
+/// Orders tasks by when they would run: more urgent first, then by
+/// `sequence_number`, then by id so that distinct tasks never compare equal.
+/// A `BinaryHeap<Reverse<Task>>` pops tasks in the same order as a
+/// [`PriorityQueue`].
+impl Ord for Task {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.priority_level
+            .cmp(&other.priority_level)
+            .then(self.sequence_number.cmp(&other.sequence_number))
+            .then(self.id.cmp(&other.id))
+    }
+}
+
+impl PartialOrd for Task {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Task {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Task {}
+
+/// Queue state right after a pop.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QueueSnapshot {
+    pub remaining: usize,
+    pub high_count: usize,
+    pub medium_count: usize,
+    pub low_count: usize,
+}
+
+impl QueueSnapshot {
+    fn count_mut(&mut self, priority_level: &PriorityLevel) -> &mut usize {
+        match priority_level {
+            PriorityLevel::High => &mut self.high_count,
+            PriorityLevel::Medium => &mut self.medium_count,
+            PriorityLevel::Low => &mut self.low_count,
+        }
+    }
+}
+
+pub trait TaskQueue {
+    fn push(&mut self, task: Task);
+    /// Removes the next task and reports what is left in the queue.
+    #[must_use = "a popped task that is dropped never runs"]
+    fn pop(&mut self) -> Option<(Task, QueueSnapshot)>;
+    #[must_use = "a popped task that is dropped never runs"]
+    fn pop_simple(&mut self) -> Option<Task> {
+        self.pop().map(|(task, _)| task)
+    }
+    #[must_use]
+    fn peek(&self) -> Option<&Task>;
+    fn len(&self) -> usize;
+    /// A length that is cheap to read but may lag behind concurrent pushes
+    /// and pops. Use `len` when the exact count matters.
+    fn len_estimate(&self) -> usize {
+        self.len()
+    }
+    fn is_empty(&self) -> bool;
+    /// How many tasks will be popped before this one; `0` means it is next.
+    fn task_position(&self, id: Uuid) -> Option<usize>;
+    /// The first task in pop order that satisfies `predicate`.
+    fn find<F: Fn(&Task) -> bool>(&self, predicate: F) -> Option<&Task>
+    where
+        Self: Sized;
+    /// Every task that satisfies `predicate`, in pop order.
+    fn find_all<F: Fn(&Task) -> bool>(&self, predicate: F) -> Vec<&Task>
+    where
+        Self: Sized;
+    fn find_by_id(&self, id: Uuid) -> Option<&Task>
+    where
+        Self: Sized,
+    {
+        self.find(|task| task.id == id)
+    }
+    /// Applies `f` to every queued task. Changes to priorities are taken into
+    /// account, so the pop order afterwards reflects them.
+    fn map_in_place<F: FnMut(&mut Task)>(&mut self, f: F)
+    where
+        Self: Sized;
+    #[cfg(feature = "std")]
+    fn handle(&mut self) -> Summary;
+
+    /// Pops the next task only if it satisfies `predicate`; otherwise the
+    /// queue is left untouched.
+    #[must_use = "a popped task that is dropped never runs"]
+    fn pop_if<F: Fn(&Task) -> bool>(&mut self, predicate: F) -> Option<Task>
+    where
+        Self: Sized,
+    {
+        if !predicate(self.peek()?) {
+            return None;
+        }
+        self.pop_simple()
+    }
+
+    /// Drains the queue on the current thread without spawning workers.
+    ///
+    /// With the `tokio` feature, handlers run inside a tokio runtime context
+    /// (a fresh one if the caller is not already in one), so they can use
+    /// `Handle::current().block_on(..)` to drive async work.
+    fn handle_blocking(&mut self) -> Summary {
+        #[cfg(feature = "tokio")]
+        if tokio::runtime::Handle::try_current().is_err() {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .build()
+                .expect("failed to build tokio runtime");
+            let _guard = runtime.enter();
+            return drain_blocking(self);
+        }
+        drain_blocking(self)
+    }
+
+    /// Executes tasks one at a time on the current thread and stops at the
+    /// first failure, leaving the remaining tasks in the queue. Skipped and
+    /// expired tasks are not failures.
+    fn handle_until_empty_or_error(&mut self) -> Result<Summary, HaltedOnError> {
+        let mut summary = Summary::default();
+        while let Some(task) = self.pop_simple() {
+            let result = catch_panics(|| task.handler.execute());
+            summary.record(&result);
+            match result {
+                Ok(_) | Err(TaskError::Skipped | TaskError::AlreadyExpired) => {}
+                Err(error) => {
+                    return Err(HaltedOnError { error, summary });
+                }
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(feature = "std")]
+fn join_workers(workers: Vec<JoinHandle<Result<i32, TaskError>>>) -> Summary {
+    let mut summary = Summary::default();
+    for worker in workers {
+        match worker.join() {
+            Ok(result) => summary.record(&result),
+            Err(_) => summary.panicked += 1,
+        }
+    }
+    summary
+}
+
+fn drain_blocking<Q: TaskQueue + ?Sized>(queue: &mut Q) -> Summary {
+    let mut summary = Summary::default();
+    while let Some(task) = queue.pop_simple() {
+        summary.record(&catch_panics(|| task.handler.execute()));
+    }
+    summary
+}
+
+type TaskHook = Box<dyn Fn(&Task) + Send + Sync>;
+type EmptyHook = Box<dyn Fn() + Send + Sync>;
+
+/// Callbacks a [`PriorityQueue`] invokes as tasks enter and leave it.
+/// `on_empty` fires when a pop leaves the queue with no tasks.
+#[derive(Default)]
+pub struct QueueObserver {
+    pub on_push: Option<TaskHook>,
+    pub on_pop: Option<TaskHook>,
+    pub on_empty: Option<EmptyHook>,
+}
+
+/// Decides a task's priority when it is pushed, for priorities that depend on
+/// state that may have changed since the task was created.
+pub trait PriorityResolver: Send + Sync {
+    fn resolve(&self, task: &Task) -> PriorityLevel;
+}
+
+#[derive(Default)]
+pub struct PriorityQueue {
+    tasks: Vec<Task>,
+    // Index of each task in `tasks`, rebuilt whenever the order changes.
+    positions: HashMap<Uuid, usize>,
+    counts: QueueSnapshot,
+    observer: Option<QueueObserver>,
+    resolver: Option<Arc<dyn PriorityResolver>>,
+    next_sequence_number: u64,
+    // Set by `IndexMut`, whose caller may have changed a task's priority or
+    // id; the next pop re-sorts.
+    dirty: bool,
+    #[cfg(feature = "std")]
+    auto_expire: bool,
+    #[cfg(feature = "std")]
+    logger: Option<Arc<dyn logger::Logger>>,
+}
+
+impl TaskQueue for PriorityQueue {
+    fn push(&mut self, mut task: Task) {
+        self.prepare(&mut task);
+        self.tasks.push(task);
+        self.sort();
+    }
+
+    fn pop(&mut self) -> Option<(Task, QueueSnapshot)> {
+        if self.dirty {
+            self.sort();
+        }
+        #[cfg(feature = "std")]
+        if self.auto_expire {
+            self.drain_expired();
+        }
+        let task = self.tasks.pop()?;
+        self.positions.remove(&task.id);
+        self.counts.remaining -= 1;
+        *self.counts.count_mut(&task.priority_level) -= 1;
+        self.notify_pop(&task);
+        Some((task, self.counts.clone()))
+    }
+
+    fn peek(&self) -> Option<&Task> {
+        self.tasks.last()
+    }
+
+    fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    fn task_position(&self, id: Uuid) -> Option<usize> {
+        let index = self.positions.get(&id)?;
+        Some(self.tasks.len() - 1 - index)
+    }
+
+    fn find<F: Fn(&Task) -> bool>(&self, predicate: F) -> Option<&Task> {
+        self.iter().find(|task| predicate(task))
+    }
+
+    fn find_all<F: Fn(&Task) -> bool>(&self, predicate: F) -> Vec<&Task> {
+        self.iter().filter(|task| predicate(task)).collect()
+    }
+
+    /// O(1), through the id index.
+    fn find_by_id(&self, id: Uuid) -> Option<&Task> {
+        self.positions.get(&id).map(|&index| &self.tasks[index])
+    }
+
+    fn map_in_place<F: FnMut(&mut Task)>(&mut self, f: F) {
+        self.tasks.iter_mut().for_each(f);
+        self.sort();
+    }
+
+    #[cfg(feature = "std")]
+    fn handle(&mut self) -> Summary {
+        self.handle_with_progress(|_, _| {})
+    }
+}
+
+impl PriorityQueue {
+    /// Like [`TaskQueue::handle`], calling `callback(completed, total)` from
+    /// the worker thread as each task finishes. `total` is the queue length
+    /// when handling starts. Calls never overlap, so `completed` goes up by
+    /// one each time.
+    #[cfg(feature = "std")]
+    pub fn handle_with_progress<F>(&mut self, callback: F) -> Summary
+    where
+        F: Fn(usize, usize) + Send + Sync + 'static,
+    {
+        let total = self.len();
+        // Held across the callback, so a worker cannot report its count
+        // before one that counted earlier has.
+        let progress = Arc::new(std::sync::Mutex::new((0, callback)));
+        let logger = self.logger();
+        let mut workers = Vec::new();
+        while let Some(task) = self.pop_simple() {
+            let correlation_id = task.child_correlation_id();
+            let handler = task.handler;
+            let priority_level = task.priority_level;
+            let progress = Arc::clone(&progress);
+            let logger = Arc::clone(&logger);
+            workers.push(spawn(move || {
+                let result = catch_panics(|| handler.execute());
+                logger.log(&format!("Task with priority {:?} (correlation {}) executed with result: {:?}", priority_level, correlation_id, result));
+                let mut progress = progress.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                let (completed, callback) = &mut *progress;
+                *completed += 1;
+                callback(*completed, total);
+                result
+            }));
+        }
+        join_workers(workers)
+    }
+
+    /// Moves the queued tasks to a background thread and handles them there.
+    /// This queue is left empty, keeping its observer and settings, and can
+    /// take new tasks straight away.
+    #[cfg(feature = "std")]
+    pub fn handle_detached(&mut self) -> DetachedHandle {
+        let mut queue = self.take();
+        queue.logger = self.logger.clone();
+        DetachedHandle {
+            join: spawn(move || queue.handle()),
+        }
+    }
+
+    /// Like [`TaskQueue::handle`], but skips each task whose handler's
+    /// [`cost_estimate`](TaskHandler::cost_estimate) says it would not finish
+    /// before the task's `expires_at`, recording it as
+    /// [`TaskError::AlreadyExpired`]. Tasks without `expires_at` always run.
+    #[cfg(feature = "std")]
+    pub fn handle_respecting_deadline_per_task(&mut self) -> Summary {
+        let mut summary = Summary::default();
+        let logger = self.logger();
+        let mut workers = Vec::new();
+        while let Some(task) = self.pop_simple() {
+            let correlation_id = task.child_correlation_id();
+            let finishes_at = std::time::SystemTime::now() + task.handler.cost_estimate();
+            if task.expires_at.is_some_and(|expires_at| finishes_at > expires_at) {
+                logger.log(&format!("Task {} (correlation {}) skipped: it would not finish before it expires", task.id, correlation_id));
+                summary.record(&Err(TaskError::AlreadyExpired));
+                continue;
+            }
+            let handler = task.handler;
+            let priority_level = task.priority_level;
+            let logger = Arc::clone(&logger);
+            workers.push(spawn(move || {
+                let result = catch_panics(|| handler.execute());
+                logger.log(&format!("Task with priority {:?} (correlation {}) executed with result: {:?}", priority_level, correlation_id, result));
+                result
+            }));
+        }
+        summary.merge(&join_workers(workers));
+        summary
+    }
+
+    /// Handles tasks one at a time on the current thread, dealing with each
+    /// failure as `strategy` says. Skipped and expired tasks are not failures.
+    #[cfg(feature = "std")]
+    pub fn handle_with_error_strategy(&mut self, strategy: ErrorStrategy) -> Summary {
+        let mut summary = Summary::default();
+        let mut requeued = hashbrown::HashSet::new();
+        let logger = self.logger();
+        while let Some(task) = self.pop_simple() {
+            let result = catch_panics(|| task.handler.execute());
+            let error = match result {
+                Ok(_) | Err(TaskError::Skipped | TaskError::AlreadyExpired) => {
+                    summary.record(&result);
+                    continue;
+                }
+                Err(error) => error,
+            };
+            match &strategy {
+                ErrorStrategy::FailFast => {
+                    summary.record(&Err(error));
+                    break;
+                }
+                ErrorStrategy::SkipAndContinue => {
+                    logger.log(&format!("Task {} (correlation {}) failed, continuing: {}", task.id, task.child_correlation_id(), error));
+                    summary.record(&Err(error));
+                }
+                ErrorStrategy::RequeueOnce if requeued.insert(task.id) => {
+                    logger.log(&format!("Task {} (correlation {}) failed, requeueing it: {}", task.id, task.child_correlation_id(), error));
+                    self.push(task);
+                }
+                ErrorStrategy::RequeueOnce => summary.record(&Err(error)),
+                ErrorStrategy::DeadLetter(dead_letters) => {
+                    summary.record(&Err(error.clone()));
+                    dead_letters.lock().unwrap().push(task, error);
+                }
+            }
+        }
+        summary
+    }
+
+    /// Pops every task and deals them out to `workers` threads in turn:
+    /// the `i`th task popped runs on worker `i % workers`. Each worker runs its
+    /// share in pop order, so the assignment is the same on every run.
+    #[cfg(feature = "std")]
+    pub fn handle_round_robin(&mut self, workers: usize) -> Summary {
+        let workers = workers.max(1);
+        let mut shares: Vec<Vec<Task>> = (0..workers).map(|_| Vec::new()).collect();
+        let mut index = 0;
+        while let Some(task) = self.pop_simple() {
+            shares[index % workers].push(task);
+            index += 1;
+        }
+
+        let logger = self.logger();
+        let handles: Vec<_> = shares
+            .into_iter()
+            .enumerate()
+            .map(|(worker_id, share)| {
+                let logger = Arc::clone(&logger);
+                spawn(move || {
+                    let mut summary = Summary::default();
+                    for task in share {
+                        let result = catch_panics(|| task.handler.execute());
+                        logger.log(&format!("Worker {} executed task {} (correlation {}) with result: {:?}", worker_id, task.id, task.child_correlation_id(), result));
+                        summary.record(&result);
+                    }
+                    summary
+                })
+            })
+            .collect();
+
+        let mut summary = Summary::default();
+        for handle in handles {
+            summary.merge(&handle.join().unwrap());
+        }
+        summary
+    }
+
+    /// Like [`TaskQueue::handle`], but each task fails with
+    /// [`TaskError::Timeout`] if it runs longer than `timeout`, whether or not
+    /// its handler is wrapped in a [`timeout::Timeout`] itself.
+    #[cfg(feature = "std")]
+    pub fn handle_with_timeout_per_task(&mut self, timeout: Duration) -> Summary {
+        let logger = self.logger();
+        let mut workers = Vec::new();
+        while let Some(task) = self.pop_simple() {
+            let correlation_id = task.child_correlation_id();
+            let priority_level = task.priority_level;
+            let handler = timeout::Timeout::<dyn TaskHandler + Send + Sync>::new(task.handler, timeout);
+            let logger = Arc::clone(&logger);
+            workers.push(spawn(move || {
+                let result = handler.execute();
+                logger.log(&format!("Task with priority {:?} (correlation {}) executed with result: {:?}", priority_level, correlation_id, result));
+                result
+            }));
+        }
+        join_workers(workers)
+    }
+
+    /// Like [`TaskQueue::handle`], but sends each task's outcome to `tx` as
+    /// soon as it finishes instead of printing it. Outcomes arrive in
+    /// completion order; a hung-up receiver does not stop the remaining tasks.
+    #[cfg(feature = "std")]
+    pub fn handle_to_channel(&mut self, tx: Sender<executor::TaskOutcome>) -> Summary {
+        let mut workers = Vec::new();
+        while let Some(task) = self.pop_simple() {
+            let task_id = task.id;
+            let handler = task.handler;
+            let tx = tx.clone();
+            workers.push(spawn(move || {
+                let result = catch_panics(|| handler.execute());
+                let _ = tx.send(executor::TaskOutcome {
+                    task_id,
+                    result: result.clone(),
+                    output: handler.take_output(),
+                });
+                result
+            }));
+        }
+        join_workers(workers)
+    }
+
+    /// Runs every task concurrently, like [`TaskQueue::handle`], and returns
+    /// their outcomes in the order the tasks were pushed (by
+    /// `sequence_number`) rather than the order they finished.
+    #[cfg(feature = "std")]
+    pub fn execute_ordered(&mut self) -> Vec<executor::TaskOutcome> {
+        let mut workers = Vec::new();
+        while let Some(task) = self.pop_simple() {
+            let task_id = task.id;
+            let handler = task.handler;
+            let worker = spawn(move || {
+                let result = catch_panics(|| handler.execute());
+                executor::TaskOutcome {
+                    task_id,
+                    result,
+                    output: handler.take_output(),
+                }
+            });
+            workers.push((task.sequence_number, worker));
+        }
+        workers.sort_by_key(|(sequence_number, _)| *sequence_number);
+        workers
+            .into_iter()
+            .map(|(_, worker)| worker.join().unwrap())
+            .collect()
+    }
+
+    pub fn new() -> Self {
+        PriorityQueue::default()
+    }
+
+    pub fn with_observer(observer: QueueObserver) -> Self {
+        PriorityQueue {
+            observer: Some(observer),
+            ..PriorityQueue::default()
+        }
+    }
+
+    /// Sends the lines the `handle_*` methods log to `logger` instead of
+    /// stdout.
+    #[cfg(feature = "std")]
+    pub fn with_logger(logger: Arc<dyn logger::Logger>) -> Self {
+        PriorityQueue {
+            logger: Some(logger),
+            ..PriorityQueue::default()
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn logger(&self) -> Arc<dyn logger::Logger> {
+        self.logger
+            .clone()
+            .unwrap_or_else(|| Arc::new(logger::WriterLogger::stdout()))
+    }
+
+    /// Every pushed task gets the priority `resolver` returns for it,
+    /// replacing the one it was built with.
+    pub fn with_resolver(resolver: Arc<dyn PriorityResolver>) -> Self {
+        PriorityQueue {
+            resolver: Some(resolver),
+            ..PriorityQueue::default()
+        }
+    }
+
+    /// Removes every task whose `expires_at` has passed and returns them, so
+    /// callers can log or dead-letter them.
+    #[cfg(feature = "std")]
+    pub fn drain_expired(&mut self) -> Vec<Task> {
+        let expired: Vec<Task> = self.tasks.extract_if(.., |task| task.is_expired()).collect();
+        if !expired.is_empty() {
+            self.sort();
+        }
+        expired
+    }
+
+    /// When enabled, every pop first discards expired tasks via
+    /// [`PriorityQueue::drain_expired`]. Off by default.
+    #[cfg(feature = "std")]
+    pub fn auto_expire_on_pop(&mut self, enable: bool) {
+        self.auto_expire = enable;
+    }
+
+    fn sort(&mut self) {
+        // Descending, so the task that runs next is last.
+        self.tasks.sort_unstable_by(|a, b| b.cmp(a));
+        self.dirty = false;
+        self.positions.clear();
+        self.counts = QueueSnapshot::default();
+        for (index, task) in self.tasks.iter().enumerate() {
+            self.positions.insert(task.id, index);
+            self.counts.remaining += 1;
+            *self.counts.count_mut(&task.priority_level) += 1;
+        }
+    }
+
+    fn notify_push(&self, task: &Task) {
+        if let Some(on_push) = self.observer.as_ref().and_then(|o| o.on_push.as_ref()) {
+            on_push(task);
+        }
+    }
+
+    fn notify_pop(&self, task: &Task) {
+        let Some(observer) = &self.observer else {
+            return;
+        };
+        if let Some(on_pop) = &observer.on_pop {
+            on_pop(task);
+        }
+        if self.tasks.is_empty() {
+            if let Some(on_empty) = &observer.on_empty {
+                on_empty();
+            }
+        }
+    }
+
+    /// Moves every task from `other` into this queue, as if each were pushed
+    /// in the order `other` would have popped them.
+    pub fn absorb(&mut self, other: PriorityQueue) {
+        for mut task in other.tasks.into_iter().rev() {
+            self.prepare(&mut task);
+            self.tasks.push(task);
+        }
+        self.sort();
+    }
+
+    /// Applies the resolver, assigns the next sequence number and notifies
+    /// the observer, for a task about to be added.
+    fn prepare(&mut self, task: &mut Task) {
+        if let Some(resolver) = &self.resolver {
+            task.priority_level = resolver.resolve(task);
+        }
+        task.sequence_number = self.next_sequence_number;
+        self.next_sequence_number += 1;
+        #[cfg(feature = "std")]
+        {
+            task.enqueued_at = Some(std::time::Instant::now());
+        }
+        self.notify_push(task);
+    }
+
+    /// Promotes every task that has waited at least `max_age` by one level
+    /// and restarts its wait, so a task climbs one level per `max_age`.
+    /// Returns how many tasks were promoted.
+    #[cfg(feature = "std")]
+    pub fn promote_older_than(&mut self, max_age: Duration) -> usize {
+        let now = std::time::Instant::now();
+        let mut promoted = 0;
+        for task in &mut self.tasks {
+            let waited = task.enqueued_at.map_or(Duration::ZERO, |enqueued_at| now.duration_since(enqueued_at));
+            if waited < max_age {
+                continue;
+            }
+            if let Some(priority_level) = task.priority_level.more_urgent() {
+                task.priority_level = priority_level;
+                task.enqueued_at = Some(now);
+                promoted += 1;
+            }
+        }
+        if promoted > 0 {
+            self.sort();
+        }
+        promoted
+    }
+
+    pub fn contains(&self, id: Uuid) -> bool {
+        self.positions.contains_key(&id)
+    }
+
+    pub fn priority_of(&self, id: Uuid) -> Option<PriorityLevel> {
+        let index = self.positions.get(&id)?;
+        Some(self.tasks[*index].priority_level)
+    }
+
+    /// Moves a queued task up one priority level. Returns `false` if the task
+    /// is not queued or is already `High`.
+    pub fn promote(&mut self, id: Uuid) -> bool {
+        self.reprioritize(id, PriorityLevel::more_urgent)
+    }
+
+    /// Moves a queued task down one priority level. Returns `false` if the
+    /// task is not queued or is already `Low`.
+    pub fn demote(&mut self, id: Uuid) -> bool {
+        self.reprioritize(id, PriorityLevel::less_urgent)
+    }
+
+    fn reprioritize(
+        &mut self,
+        id: Uuid,
+        change: fn(PriorityLevel) -> Option<PriorityLevel>,
+    ) -> bool {
+        let Some(&index) = self.positions.get(&id) else {
+            return false;
+        };
+        let Some(priority_level) = change(self.tasks[index].priority_level) else {
+            return false;
+        };
+        self.tasks[index].priority_level = priority_level;
+        self.sort();
+        true
+    }
+
+    /// Same as [`TaskQueue::pop_simple`], for call sites that also use
+    /// [`PriorityQueue::pop_lowest`].
+    #[must_use = "a popped task that is dropped never runs"]
+    pub fn pop_highest(&mut self) -> Option<Task> {
+        self.pop_simple()
+    }
+
+    /// Removes the task with this id, whatever its position, as a pop would.
+    /// O(n).
+    #[must_use = "a popped task that is dropped never runs"]
+    pub fn pop_by_id(&mut self, id: Uuid) -> Option<Task> {
+        if self.dirty {
+            self.sort();
+        }
+        let index = *self.positions.get(&id)?;
+        let task = self.tasks.remove(index);
+        if index == self.tasks.len() {
+            // The next task: nothing else moved.
+            self.positions.remove(&task.id);
+            self.counts.remaining -= 1;
+            *self.counts.count_mut(&task.priority_level) -= 1;
+        } else {
+            self.sort();
+        }
+        self.notify_pop(&task);
+        Some(task)
+    }
+
+    /// Removes the least urgent task, e.g. to evict it from a full queue. Of
+    /// the lowest-priority tasks, this is the one that would run last.
+    ///
+    /// This is O(n): the remaining tasks are shifted and reindexed.
+    #[must_use = "a popped task that is dropped never runs"]
+    pub fn pop_lowest(&mut self) -> Option<Task> {
+        if self.dirty {
+            self.sort();
+        }
+        #[cfg(feature = "std")]
+        if self.auto_expire {
+            self.drain_expired();
+        }
+        if self.tasks.is_empty() {
+            return None;
+        }
+        let task = self.tasks.remove(0);
+        self.sort();
+        self.notify_pop(&task);
+        Some(task)
+    }
+
+    #[must_use]
+    pub fn peek_lowest(&self) -> Option<&Task> {
+        self.tasks.first()
+    }
+
+    /// The handler of the task with `id`, if it is queued and its handler is a
+    /// `T` that exposes itself through [`TaskHandler::as_any`].
+    pub fn inspect_handler<T: 'static>(&self, id: Uuid) -> Option<&T> {
+        self.find_by_id(id)?.handler.as_any()?.downcast_ref()
+    }
+
+    pub fn iter(&self) -> core::iter::Rev<core::slice::Iter<'_, Task>> {
+        self.tasks.iter().rev()
+    }
+
+    /// Sends the next `n` tasks to the back of their priority level, as if
+    /// they had just been pushed, so tasks of equal priority take turns.
+    /// Priority order is kept: a `High` task never moves behind a `Low` one.
+    pub fn rotate(&mut self, n: usize) {
+        if self.dirty {
+            self.sort();
+        }
+        let start = self.tasks.len() - n.min(self.tasks.len());
+        for index in (start..self.tasks.len()).rev() {
+            self.tasks[index].sequence_number = self.next_sequence_number;
+            self.next_sequence_number += 1;
+        }
+        self.sort();
+    }
+
+    /// Exchanges the tasks of the two queues in O(1), without moving any task.
+    /// Observers, resolvers and other settings stay with their queue.
+    pub fn swap(&mut self, other: &mut PriorityQueue) {
+        if self.dirty {
+            self.sort();
+        }
+        if other.dirty {
+            other.sort();
+        }
+        core::mem::swap(&mut self.tasks, &mut other.tasks);
+        core::mem::swap(&mut self.positions, &mut other.positions);
+        core::mem::swap(&mut self.counts, &mut other.counts);
+        // Keep tasks pushed from now on behind the ones that just arrived.
+        let next_sequence_number = self.next_sequence_number.max(other.next_sequence_number);
+        self.next_sequence_number = next_sequence_number;
+        other.next_sequence_number = next_sequence_number;
+    }
+
+    /// Moves every task into a new queue with default settings, leaving this
+    /// one empty. O(1), like [`PriorityQueue::swap`].
+    pub fn take(&mut self) -> PriorityQueue {
+        let mut taken = PriorityQueue::new();
+        self.swap(&mut taken);
+        taken
+    }
+
+    /// The next `k` tasks to run (fewer if the queue is shorter), most urgent
+    /// first. O(k), since the tasks are already kept in order.
+    pub fn top_k(&self, k: usize) -> Vec<&Task> {
+        self.iter().take(k).collect()
+    }
+
+    /// Stricter than `==`: both queues must pop the same task ids at the same
+    /// priorities in the same order.
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use task_handler::{HardProblem, PriorityLevel, PriorityQueue, Task, TaskQueue};
+    /// use uuid::Uuid;
+    ///
+    /// let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+    /// let task = |id, priority_level| {
+    ///     let mut task = Task::new(HardProblem::new(1, 2), priority_level);
+    ///     task.id = id;
+    ///     task
+    /// };
+    ///
+    /// let mut a = PriorityQueue::new();
+    /// a.push(task(first, PriorityLevel::High));
+    /// a.push(task(second, PriorityLevel::Low));
+    ///
+    /// let mut b = PriorityQueue::new();
+    /// b.push(task(second, PriorityLevel::High));
+    /// b.push(task(first, PriorityLevel::Low));
+    ///
+    /// assert!(a == b);
+    /// assert!(!PriorityQueue::same_order(&a, &b));
+    /// # }
+    /// ```
+    pub fn same_order(a: &PriorityQueue, b: &PriorityQueue) -> bool {
+        a.len() == b.len()
+            && a
+                .iter()
+                .zip(b.iter())
+                .all(|(x, y)| x.id == y.id && x.priority_level == y.priority_level)
+    }
+
+    /// Moves every task out of this queue, returning the tasks at `level` or
+    /// more urgent in the first queue and the less urgent ones in the second.
+    pub fn split_at_priority(&mut self, level: PriorityLevel) -> (PriorityQueue, PriorityQueue) {
+        self.positions.clear();
+        self.counts = QueueSnapshot::default();
+        let (urgent, rest): (Vec<Task>, Vec<Task>) = self
+            .tasks
+            .drain(..)
+            .partition(|task| task.priority_level <= level);
+        let next_sequence_number = self.next_sequence_number;
+        (
+            PriorityQueue::from_tasks(urgent, next_sequence_number),
+            PriorityQueue::from_tasks(rest, next_sequence_number),
+        )
+    }
+
+    /// The tasks keep their sequence numbers, so `next_sequence_number` must
+    /// be past all of them for later pushes to queue up behind them.
+    fn from_tasks(tasks: Vec<Task>, next_sequence_number: u64) -> Self {
+        let mut queue = PriorityQueue {
+            tasks,
+            next_sequence_number,
+            ..PriorityQueue::default()
+        };
+        queue.sort();
+        queue
+    }
+}
+
+/// Pops tasks in priority order until the queue is empty.
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use task_handler::{HardProblem, PriorityLevel, PriorityQueue, Task, TaskQueue};
+///
+/// let mut queue = PriorityQueue::new();
+/// queue.push((HardProblem::new(1, 2), PriorityLevel::Low).into());
+/// queue.push((HardProblem::new(3, 4), PriorityLevel::High).into());
+///
+/// let levels: Vec<PriorityLevel> = queue.into_iter().map(|task| task.priority_level).collect();
+/// assert_eq!(levels, [PriorityLevel::High, PriorityLevel::Low]);
+/// # }
+/// ```
+///
+/// The loop consumes the queue, so it cannot be used afterwards:
+///
+/// ```compile_fail
+/// use task_handler::{PriorityQueue, TaskQueue};
+///
+/// let queue = PriorityQueue::new();
+/// for _task in queue {}
+/// queue.len();
+/// ```
+pub struct IntoIter(PriorityQueue);
+
+impl Iterator for IntoIter {
+    type Item = Task;
+
+    fn next(&mut self) -> Option<Task> {
+        self.0.pop_simple()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len(), Some(self.0.len()))
+    }
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+impl IntoIterator for PriorityQueue {
+    type Item = Task;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        IntoIter(self)
+    }
+}
+
+/// Borrows the tasks in priority order without removing them.
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use task_handler::{HardProblem, PriorityLevel, PriorityQueue, TaskQueue};
+///
+/// let mut queue = PriorityQueue::new();
+/// queue.push((HardProblem::new(1, 2), PriorityLevel::Low).into());
+/// queue.push((HardProblem::new(3, 4), PriorityLevel::High).into());
+///
+/// for task in &queue {
+///     println!("{} {:?}", task.id, task.priority_level);
+/// }
+/// assert_eq!(queue.len(), 2);
+/// # }
+/// ```
+impl<'a> IntoIterator for &'a PriorityQueue {
+    type Item = &'a Task;
+    type IntoIter = core::iter::Rev<core::slice::Iter<'a, Task>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Queues are equal when they hold the same task ids, regardless of order or
+/// priority. Use [`PriorityQueue::same_order`] to compare pop order too.
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use task_handler::{HardProblem, PriorityLevel, PriorityQueue, Task, TaskQueue};
+///
+/// let task = Task::new(HardProblem::new(1, 2), PriorityLevel::Medium);
+/// let mut copy = Task::new(HardProblem::new(3, 4), PriorityLevel::Medium);
+/// copy.id = task.id;
+///
+/// let mut a = PriorityQueue::new();
+/// a.push(task);
+/// let mut b = PriorityQueue::new();
+/// b.push(copy);
+/// assert!(a == b);
+///
+/// b.push(Task::new(HardProblem::new(5, 6), PriorityLevel::High));
+/// assert!(a != b);
+/// # }
+/// ```
+impl PartialEq for PriorityQueue {
+    fn eq(&self, other: &PriorityQueue) -> bool {
+        // Read the ids off the tasks, since `positions` can be stale after an
+        // `IndexMut` change.
+        let sorted_ids = |queue: &PriorityQueue| {
+            let mut ids: Vec<Uuid> = queue.tasks.iter().map(|task| task.id).collect();
+            ids.sort_unstable();
+            ids
+        };
+        self.tasks.len() == other.tasks.len() && sorted_ids(self) == sorted_ids(other)
+    }
+}
+
+impl Eq for PriorityQueue {}
+
+/// The task at `index` in pop order, so `queue[0]` is the next to run. O(1).
+///
+/// Panics if `index >= queue.len()`.
+impl Index<usize> for PriorityQueue {
+    type Output = Task;
+
+    fn index(&self, index: usize) -> &Task {
+        let len = self.tasks.len();
+        assert!(index < len, "index {} out of range for queue of length {}", index, len);
+        &self.tasks[len - 1 - index]
+    }
+}
+
+/// Changes to the task's priority or id take effect at the next push or pop.
+/// Until then, `peek`, `iter`, indexing and lookups by id see the old order.
+impl IndexMut<usize> for PriorityQueue {
+    fn index_mut(&mut self, index: usize) -> &mut Task {
+        let len = self.tasks.len();
+        assert!(index < len, "index {} out of range for queue of length {}", index, len);
+        self.dirty = true;
+        &mut self.tasks[len - 1 - index]
+    }
+}
+
+/// Orders queues by the priority of the task each would pop next, with the
+/// most urgent queue greatest and empty queues least, so a
+/// `BinaryHeap<ComparablePriorityQueue>` serves the most urgent queue first.
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use std::collections::BinaryHeap;
+/// use task_handler::{ComparablePriorityQueue, HardProblem, PriorityLevel, PriorityQueue, TaskQueue};
+///
+/// let mut low = PriorityQueue::new();
+/// low.push((HardProblem::new(1, 2), PriorityLevel::Low).into());
+/// let mut high = PriorityQueue::new();
+/// high.push((HardProblem::new(3, 4), PriorityLevel::High).into());
+///
+/// let mut heap = BinaryHeap::new();
+/// heap.push(ComparablePriorityQueue(low));
+/// heap.push(ComparablePriorityQueue(high));
+/// assert_eq!(heap.pop().unwrap().0.peek().unwrap().priority_level, PriorityLevel::High);
+/// # }
+/// ```
+pub struct ComparablePriorityQueue(pub PriorityQueue);
+
+impl ComparablePriorityQueue {
+    fn urgency(&self) -> Option<Reverse<PriorityLevel>> {
+        self.0.peek().map(|task| Reverse(task.priority_level))
+    }
+}
+
+impl PartialEq for ComparablePriorityQueue {
+    fn eq(&self, other: &ComparablePriorityQueue) -> bool {
+        self.urgency() == other.urgency()
+    }
+}
+
+impl Eq for ComparablePriorityQueue {}
+
+impl PartialOrd for ComparablePriorityQueue {
+    fn partial_cmp(&self, other: &ComparablePriorityQueue) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ComparablePriorityQueue {
+    fn cmp(&self, other: &ComparablePriorityQueue) -> core::cmp::Ordering {
+        self.urgency().cmp(&other.urgency())
+    }
+}
+
+impl AddAssign<PriorityQueue> for PriorityQueue {
+    fn add_assign(&mut self, rhs: PriorityQueue) {
+        self.absorb(rhs);
+    }
+}
+
+impl Add<PriorityQueue> for PriorityQueue {
+    type Output = PriorityQueue;
+
+    fn add(mut self, rhs: PriorityQueue) -> PriorityQueue {
+        self.absorb(rhs);
+        self
+    }
+}
+
+/// Drops every task whose id is also queued in `rhs`; the tasks in `rhs`
+/// are dropped too.
+impl SubAssign<PriorityQueue> for PriorityQueue {
+    fn sub_assign(&mut self, rhs: PriorityQueue) {
+        self.tasks.retain(|task| !rhs.contains(task.id));
+        self.sort();
+    }
+}
+
+impl Sub<PriorityQueue> for PriorityQueue {
+    type Output = PriorityQueue;
+
+    fn sub(mut self, rhs: PriorityQueue) -> PriorityQueue {
+        self -= rhs;
+        self
+    }
+}
+
+impl Extend<PriorityQueue> for PriorityQueue {
+    fn extend<I: IntoIterator<Item = PriorityQueue>>(&mut self, iter: I) {
+        for queue in iter {
+            self.absorb(queue);
+        }
+    }
+}
+
+/// Wrapped in [`Reverse`] because `BinaryHeap` pops its greatest element and
+/// [`Task`]'s `Ord` puts the most urgent task first: the heap then pops tasks
+/// in the same order as the queue would.
+impl From<PriorityQueue> for BinaryHeap<Reverse<Task>> {
+    fn from(mut queue: PriorityQueue) -> Self {
+        core::mem::take(&mut queue.tasks).into_iter().map(Reverse).collect()
+    }
+}
+
+/// Keeps the heap's pop order, assigning fresh sequence numbers as if the
+/// tasks had been pushed in that order.
+impl From<BinaryHeap<Reverse<Task>>> for PriorityQueue {
+    fn from(heap: BinaryHeap<Reverse<Task>>) -> Self {
+        let mut queue = PriorityQueue::new();
+        for Reverse(mut task) in heap.into_sorted_vec().into_iter().rev() {
+            queue.prepare(&mut task);
+            queue.tasks.push(task);
+        }
+        queue.sort();
+        queue
+    }
+}
+
+/// A plain `BinaryHeap<Task>` pops its greatest task, which by [`Task`]'s
+/// `Ord` is the least urgent: its pop order is the reverse of the queue's,
+/// while `into_sorted_vec` lists the tasks in the queue's pop order. Convert
+/// to `BinaryHeap<Reverse<Task>>` to pop in the same order as the queue.
+impl From<PriorityQueue> for BinaryHeap<Task> {
+    fn from(mut queue: PriorityQueue) -> Self {
+        core::mem::take(&mut queue.tasks).into_iter().collect()
+    }
+}
+
+/// Keeps the order of `into_sorted_vec`, most urgent first, assigning fresh
+/// sequence numbers as if the tasks had been pushed in that order.
+impl From<BinaryHeap<Task>> for PriorityQueue {
+    fn from(heap: BinaryHeap<Task>) -> Self {
+        let mut queue = PriorityQueue::new();
+        for mut task in heap.into_sorted_vec() {
+            queue.prepare(&mut task);
+            queue.tasks.push(task);
+        }
+        queue.sort();
+        queue
+    }
+}
+
+/// Blocks until every sender has been dropped, pushing each task received.
+#[cfg(feature = "std")]
+impl From<Receiver<Task>> for PriorityQueue {
+    fn from(rx: Receiver<Task>) -> Self {
+        let mut queue = PriorityQueue::new();
+        while let Ok(task) = rx.recv() {
+            queue.push(task);
+        }
+        queue
+    }
+}
+
+/// Pushes tasks from `rx` into `queue` until none arrives for `timeout` or
+/// every sender has been dropped, and returns how many were pushed.
+#[cfg(feature = "std")]
+pub fn drain_channel_into(rx: &Receiver<Task>, queue: &mut dyn TaskQueue, timeout: Duration) -> usize {
+    let mut pushed = 0;
+    while let Ok(task) = rx.recv_timeout(timeout) {
+        queue.push(task);
+        pushed += 1;
+    }
+    pushed
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    #[default]
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+pub struct HardProblem<T>
+{
+    num1: T,
+    num2: T,
+    op: Operation,
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    delay: Duration,
+}
+
+impl<T> HardProblem<T>
+where T: AddAssign + Sub<Output = T> + Mul<Output = T> + Div<Output = T> + Into<i32> + Clone + Display
+{
+    pub fn new(num1: T, num2: T) -> Self {
+        HardProblem { num1, num2, op: Operation::Add, delay: Duration::from_secs(1) }
+    }
+
+    pub fn with_operation(mut self, op: Operation) -> Self {
+        self.op = op;
+        self
+    }
+
+    pub fn num1(&self) -> &T {
+        &self.num1
+    }
+
+    pub fn num2(&self) -> &T {
+        &self.num2
+    }
+
+    /// Replaces the simulated one second of work done by `solve`.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Applies the operation to the two numbers. Dividing by zero fails with
+    /// [`TaskError::Failed`].
+    pub fn solve(&self) -> Result<i32, TaskError> {
+        let (num1, num2) = (self.num1.clone(), self.num2.clone());
+        let result = match self.op {
+            Operation::Add => {
+                let mut result = num1;
+                result += num2;
+                result
+            }
+            Operation::Sub => num1 - num2,
+            Operation::Mul => num1 * num2,
+            Operation::Div => {
+                if num2.clone().into() == 0 {
+                    return Err(TaskError::Failed(String::from("division by zero")));
+                }
+                num1 / num2
+            }
+        };
+        let int_result: i32 = result.into();
+        #[cfg(feature = "std")]
+        sleep(self.delay);
+        Ok(int_result)
+    }
+}
+
+impl<T> TaskHandler for HardProblem<T>
+where T: AddAssign + Sub<Output = T> + Mul<Output = T> + Div<Output = T> + Into<i32> + Clone + Display + 'static {
+    fn execute(&self) -> Result<i32, TaskError> {
+        self.solve()
+    }
+
+    fn handler_type_name(&self) -> &'static str {
+        "HardProblem"
+    }
+
+    fn cost_estimate(&self) -> Duration {
+        self.delay
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// Wraps the problem in a `Low` priority task with a fresh id.
+///
+/// ```
+/// use task_handler::{HardProblem, PriorityLevel, Task};
+///
+/// let t: Task = HardProblem::new(1, 2).into();
+/// assert_eq!(t.priority_level, PriorityLevel::Low);
+/// ```
+#[cfg(feature = "std")]
+impl<T> From<HardProblem<T>> for Task
+where T: AddAssign + Sub<Output = T> + Mul<Output = T> + Div<Output = T> + Into<i32> + Clone + Display + Send + Sync + 'static {
+    fn from(problem: HardProblem<T>) -> Self {
+        Task::from((problem, PriorityLevel::Low))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> From<(HardProblem<T>, PriorityLevel)> for Task
+where T: AddAssign + Sub<Output = T> + Mul<Output = T> + Div<Output = T> + Into<i32> + Clone + Display + Send + Sync + 'static {
+    fn from((problem, priority_level): (HardProblem<T>, PriorityLevel)) -> Self {
+        Task::new(problem, priority_level)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    /// Finishes straight away with `result`.
+    struct Returns(Result<i32, TaskError>);
+
+    impl TaskHandler for Returns {
+        fn execute(&self) -> Result<i32, TaskError> {
+            self.0.clone()
+        }
+    }
+
+    fn task(result: Result<i32, TaskError>, priority_level: PriorityLevel) -> Task {
+        Task::new(Returns(result), priority_level)
+    }
+
+    fn pop_ids<Q: TaskQueue>(queue: &mut Q) -> Vec<Uuid> {
+        core::iter::from_fn(|| queue.pop_simple()).map(|task| task.id).collect()
+    }
+
+    #[test]
+    fn absorb_orders_both_queues_as_if_pushed_in_turn() {
+        use PriorityLevel::{High, Low, Medium};
+        let mut pushed = Vec::new();
+        let mut fill = |levels: [PriorityLevel; 5]| {
+            let mut queue = PriorityQueue::new();
+            for level in levels {
+                let task = task(Ok(0), level);
+                pushed.push((level, task.id));
+                queue.push(task);
+            }
+            queue
+        };
+        let mut first = fill([Low, High, Medium, Low, High]);
+        let second = fill([Medium, Low, High, High, Medium]);
+
+        first.absorb(second);
+
+        assert_eq!(first.len(), 10);
+        // Stable, so tasks of one level keep the order they were pushed in.
+        pushed.sort_by_key(|(level, _)| *level);
+        let expected: Vec<Uuid> = pushed.into_iter().map(|(_, id)| id).collect();
+        assert_eq!(pop_ids(&mut first), expected);
+    }
+
+    #[test]
+    fn handle_until_empty_or_error_stops_at_the_first_failure() {
+        let mut queue = PriorityQueue::new();
+        for index in 0..5 {
+            let result = if index == 2 { Err(TaskError::Failed("third".into())) } else { Ok(index) };
+            queue.push(task(result, PriorityLevel::Medium));
+        }
+
+        let halted = queue.handle_until_empty_or_error().unwrap_err();
+
+        assert_eq!(halted.error, TaskError::Failed("third".into()));
+        assert_eq!(halted.summary.executed, 2);
+        assert_eq!(halted.summary.failed, 1);
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[test]
+    fn handle_until_empty_or_error_passes_skipped_and_expired_tasks() {
+        let mut queue = PriorityQueue::new();
+        queue.push(task(Err(TaskError::Skipped), PriorityLevel::Medium));
+        queue.push(task(Err(TaskError::AlreadyExpired), PriorityLevel::Medium));
+        queue.push(task(Ok(1), PriorityLevel::Medium));
+
+        let summary = queue.handle_until_empty_or_error().unwrap();
+
+        assert_eq!(summary, Summary { executed: 1, failed: 0, skipped: 2, panicked: 0 });
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn handle_blocking_runs_on_the_calling_thread() {
+        struct OnThread(std::thread::ThreadId);
+
+        impl TaskHandler for OnThread {
+            fn execute(&self) -> Result<i32, TaskError> {
+                if std::thread::current().id() == self.0 { Ok(1) } else { Err(TaskError::Failed("other thread".into())) }
+            }
+        }
+
+        let mut queue = PriorityQueue::new();
+        for _ in 0..3 {
+            queue.push(Task::new(OnThread(std::thread::current().id()), PriorityLevel::Medium));
+        }
+        queue.push(task(Err(TaskError::Failed("boom".into())), PriorityLevel::Low));
+
+        let summary = queue.handle_blocking();
+
+        assert_eq!(summary, Summary { executed: 3, failed: 1, skipped: 0, panicked: 0 });
+        assert!(queue.is_empty());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn handle_blocking_provides_a_tokio_runtime() {
+        struct AsyncWork;
+
+        impl TaskHandler for AsyncWork {
+            fn execute(&self) -> Result<i32, TaskError> {
+                let runtime = tokio::runtime::Handle::current();
+                runtime.block_on(runtime.spawn(async { 5 })).map_err(|err| TaskError::Failed(err.to_string()))
+            }
+        }
+
+        let mut queue = PriorityQueue::new();
+        queue.push(Task::new(AsyncWork, PriorityLevel::High));
+        queue.push(Task::new(AsyncWork, PriorityLevel::Low));
+
+        assert_eq!(queue.handle_blocking().executed, 2);
+    }
+
+    #[test]
+    fn panics_are_counted_apart_from_failures() {
+        struct Panics;
+
+        impl TaskHandler for Panics {
+            fn execute(&self) -> Result<i32, TaskError> {
+                panic!("test panic")
+            }
+        }
+
+        assert_eq!(catch_panics(|| Panics.execute()), Err(TaskError::HandlerPanic("test panic".into())));
+        assert_eq!(catch_panics(|| panic!("{} panic", "formatted")), Err(TaskError::HandlerPanic("formatted panic".into())));
+
+        let mut queue = PriorityQueue::with_logger(Arc::new(logger::WriterLogger::new(Vec::new())));
+        queue.push(Task::new(Panics, PriorityLevel::High));
+        queue.push(task(Ok(1), PriorityLevel::Low));
+
+        let summary = queue.handle();
+
+        assert_eq!(summary, Summary { executed: 1, failed: 0, skipped: 0, panicked: 1 });
+    }
+
+    #[test]
+    fn progress_is_reported_once_per_task_in_order() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut queue = PriorityQueue::with_logger(Arc::new(logger::WriterLogger::new(Vec::new())));
+        for index in 0..5 {
+            queue.push(task(Ok(index), PriorityLevel::Medium));
+        }
+
+        let recorded = Arc::clone(&calls);
+        let summary = queue.handle_with_progress(move |completed, total| recorded.lock().unwrap().push((completed, total)));
+
+        assert_eq!(summary.executed, 5);
+        assert_eq!(*calls.lock().unwrap(), [(1, 5), (2, 5), (3, 5), (4, 5), (5, 5)]);
+    }
+
+    /// Fails the first `failures` times it runs.
+    struct FailsTimes(std::sync::Mutex<u32>);
+
+    impl TaskHandler for FailsTimes {
+        fn execute(&self) -> Result<i32, TaskError> {
+            let mut failures = self.0.lock().unwrap();
+            if *failures == 0 {
+                return Ok(1);
+            }
+            *failures -= 1;
+            Err(TaskError::Failed("flaky".into()))
+        }
+    }
+
+    /// A failing Medium task between two succeeding ones, High and Low.
+    fn failing_in_the_middle(failures: u32) -> PriorityQueue {
+        let mut queue = PriorityQueue::with_logger(Arc::new(logger::WriterLogger::new(Vec::new())));
+        queue.push(task(Ok(1), PriorityLevel::High));
+        queue.push(Task::new(FailsTimes(std::sync::Mutex::new(failures)), PriorityLevel::Medium));
+        queue.push(task(Ok(1), PriorityLevel::Low));
+        queue
+    }
+
+    #[test]
+    fn fail_fast_leaves_the_rest_queued() {
+        let mut queue = failing_in_the_middle(1);
+        let summary = queue.handle_with_error_strategy(ErrorStrategy::FailFast);
+        assert_eq!(summary, Summary { executed: 1, failed: 1, skipped: 0, panicked: 0 });
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn skip_and_continue_runs_everything() {
+        let mut queue = failing_in_the_middle(1);
+        let summary = queue.handle_with_error_strategy(ErrorStrategy::SkipAndContinue);
+        assert_eq!(summary, Summary { executed: 2, failed: 1, skipped: 0, panicked: 0 });
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn requeue_once_retries_a_task_a_single_time() {
+        let mut queue = failing_in_the_middle(1);
+        let summary = queue.handle_with_error_strategy(ErrorStrategy::RequeueOnce);
+        assert_eq!(summary, Summary { executed: 3, failed: 0, skipped: 0, panicked: 0 });
+
+        let mut queue = failing_in_the_middle(2);
+        let summary = queue.handle_with_error_strategy(ErrorStrategy::RequeueOnce);
+        assert_eq!(summary, Summary { executed: 2, failed: 1, skipped: 0, panicked: 0 });
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn dead_letter_keeps_the_failed_task() {
+        let dead_letters = Arc::new(std::sync::Mutex::new(dead_letter::DeadLetterQueue::new()));
+        let mut queue = failing_in_the_middle(1);
+        let failing_id = queue.find(|task| task.priority_level == PriorityLevel::Medium).unwrap().id;
+
+        let summary = queue.handle_with_error_strategy(ErrorStrategy::DeadLetter(Arc::clone(&dead_letters)));
+
+        assert_eq!(summary, Summary { executed: 2, failed: 1, skipped: 0, panicked: 0 });
+        let mut dead_letters = dead_letters.lock().unwrap();
+        let entries: Vec<_> = dead_letters.iter().map(|(task, error)| (task.id, error.clone())).collect();
+        assert_eq!(entries, [(failing_id, TaskError::Failed("flaky".into()))]);
+
+        // Retried from the dead letter queue, it now succeeds.
+        assert_eq!(dead_letters.retry_into(&mut queue), 1);
+        assert_eq!(queue.handle_with_error_strategy(ErrorStrategy::FailFast).executed, 1);
+    }
+
+    /// The same tasks, built twice with the same ids and sequence numbers.
+    fn twin_tasks(levels: &[PriorityLevel]) -> (Vec<Task>, Vec<Task>) {
+        let make = |(index, &level): (usize, &PriorityLevel)| {
+            let mut task = Task::with_id(Uuid::from_u128(index as u128 + 1), Box::new(Returns(Ok(0))), level);
+            task.sequence_number = index as u64;
+            task
+        };
+        (levels.iter().enumerate().map(make).collect(), levels.iter().enumerate().map(make).collect())
+    }
+
+    #[test]
+    fn btree_set_iterates_in_pop_order() {
+        use PriorityLevel::{High, Low, Medium};
+        let (for_set, for_queue) = twin_tasks(&[Low, High, Medium, High, Low, Medium]);
+        let set: alloc::collections::BTreeSet<Task> = for_set.into_iter().collect();
+        let mut queue = PriorityQueue::new();
+        for task in for_queue {
+            queue.push(task);
+        }
+
+        let from_set: Vec<Uuid> = set.iter().map(|task| task.id).collect();
+        assert_eq!(set.len(), 6);
+        assert_eq!(from_set, pop_ids(&mut queue));
+        assert_eq!(from_set.first(), Some(&Uuid::from_u128(2)));
+    }
+
+    #[test]
+    fn binary_heaps_pop_in_queue_order() {
+        use PriorityLevel::{High, Low, Medium};
+        let levels = [Medium, Low, High, Medium, High];
+        let (first, second) = twin_tasks(&levels);
+        let (third, fourth) = twin_tasks(&levels);
+        let fill = |tasks: Vec<Task>| {
+            let mut queue = PriorityQueue::new();
+            for task in tasks {
+                queue.push(task);
+            }
+            queue
+        };
+        let expected = pop_ids(&mut fill(first));
+
+        let mut reversed: BinaryHeap<Reverse<Task>> = fill(second).into();
+        let from_reversed: Vec<Uuid> = core::iter::from_fn(|| reversed.pop()).map(|Reverse(task)| task.id).collect();
+        assert_eq!(from_reversed, expected);
+
+        // A plain heap pops the least urgent task first.
+        let mut plain: BinaryHeap<Task> = fill(third).into();
+        let mut from_plain: Vec<Uuid> = core::iter::from_fn(|| plain.pop()).map(|task| task.id).collect();
+        from_plain.reverse();
+        assert_eq!(from_plain, expected);
+
+        let heap: BinaryHeap<Task> = fill(fourth).into();
+        let mut back = PriorityQueue::from(heap);
+        assert_eq!(pop_ids(&mut back), expected);
+        let mut back = PriorityQueue::from(BinaryHeap::<Reverse<Task>>::new());
+        assert!(back.pop().is_none());
+    }
+
+    fn expiring(result: Result<i32, TaskError>, priority_level: PriorityLevel, expires_at: std::time::SystemTime) -> Task {
+        let mut task = task(result, priority_level);
+        task.expires_at = Some(expires_at);
+        task
+    }
+
+    fn past() -> std::time::SystemTime {
+        std::time::SystemTime::now() - Duration::from_secs(1)
+    }
+
+    fn future() -> std::time::SystemTime {
+        std::time::SystemTime::now() + Duration::from_secs(60)
+    }
+
+    #[test]
+    fn drain_expired_removes_only_expired_tasks() {
+        let mut queue = PriorityQueue::new();
+        let mut expired_ids = Vec::new();
+        for level in [PriorityLevel::High, PriorityLevel::Medium, PriorityLevel::Low] {
+            let task = expiring(Ok(0), level, past());
+            expired_ids.push(task.id);
+            queue.push(task);
+        }
+
+        let drained: Vec<Uuid> = queue.drain_expired().into_iter().map(|task| task.id).collect();
+        assert_eq!(queue.len(), 0);
+        assert_eq!(drained.len(), 3);
+        assert!(expired_ids.iter().all(|id| drained.contains(id)));
+
+        let kept = expiring(Ok(0), PriorityLevel::Low, future());
+        let kept_id = kept.id;
+        queue.push(kept);
+        queue.push(expiring(Ok(0), PriorityLevel::High, past()));
+        queue.push(task(Ok(0), PriorityLevel::Medium));
+        assert_eq!(queue.drain_expired().len(), 1);
+        assert_eq!(queue.len(), 2);
+        assert!(queue.contains(kept_id));
+        assert!(queue.drain_expired().is_empty());
+    }
+
+    #[test]
+    fn auto_expire_on_pop_skips_expired_tasks() {
+        let mut queue = PriorityQueue::new();
+        queue.push(expiring(Ok(1), PriorityLevel::High, past()));
+        let live = task(Ok(2), PriorityLevel::Low);
+        let live_id = live.id;
+        queue.push(live);
+
+        // Off by default: the expired task is still popped first.
+        assert!(queue.peek().is_some_and(|task| task.is_expired()));
+
+        queue.auto_expire_on_pop(true);
+        let (popped, snapshot) = queue.pop().unwrap();
+        assert_eq!(popped.id, live_id);
+        assert_eq!(snapshot.remaining, 0);
+        assert!(queue.is_empty());
+
+        queue.push(expiring(Ok(1), PriorityLevel::High, past()));
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn hard_problem_applies_each_operation() {
+        let solve = |op| HardProblem::new(12, 4).with_operation(op).with_delay(Duration::ZERO).solve();
+        assert_eq!(solve(Operation::Add), Ok(16));
+        assert_eq!(solve(Operation::Sub), Ok(8));
+        assert_eq!(solve(Operation::Mul), Ok(48));
+        assert_eq!(solve(Operation::Div), Ok(3));
+        assert_eq!(HardProblem::new(7, 2).with_delay(Duration::ZERO).solve(), Ok(9));
+        assert_eq!(HardProblem::new(7i8, 2).with_operation(Operation::Div).with_delay(Duration::ZERO).solve(), Ok(3));
+    }
+
+    #[test]
+    fn hard_problem_rejects_division_by_zero() {
+        let problem = HardProblem::new(5, 0).with_operation(Operation::Div).with_delay(Duration::ZERO);
+        assert_eq!(problem.execute(), Err(TaskError::Failed("division by zero".into())));
+        // Only division cares about a zero divisor.
+        assert_eq!(HardProblem::new(5, 0).with_operation(Operation::Mul).with_delay(Duration::ZERO).solve(), Ok(0));
+    }
+
+    #[test]
+    fn queues_with_the_same_ids_are_equal_in_any_order() {
+        let (tasks, twins) = twin_tasks(&[PriorityLevel::High, PriorityLevel::Low, PriorityLevel::Medium]);
+        let mut a = PriorityQueue::new();
+        let mut b = PriorityQueue::new();
+        for task in tasks {
+            a.push(task);
+        }
+        for mut twin in twins.into_iter().rev() {
+            twin.priority_level = PriorityLevel::Medium;
+            b.push(twin);
+        }
+        assert!(a == b);
+        assert!(!PriorityQueue::same_order(&a, &b));
+        assert!(PriorityQueue::same_order(&a, &a));
+
+        assert!(b.pop_simple().is_some());
+        assert!(a != b);
+        assert!(PriorityQueue::new() == PriorityQueue::new());
+    }
+
+    #[test]
+    fn equality_sees_ids_changed_through_index_mut() {
+        let (tasks, twins) = twin_tasks(&[PriorityLevel::High, PriorityLevel::Low]);
+        let mut a = PriorityQueue::new();
+        let mut b = PriorityQueue::new();
+        for (task, twin) in tasks.into_iter().zip(twins) {
+            a.push(task);
+            b.push(twin);
+        }
+        assert!(a == b);
+
+        b[0].id = Uuid::from_u128(99);
+        assert!(a != b);
+        b[0].id = a[0].id;
+        assert!(a == b);
+        assert!(PriorityQueue::same_order(&a, &b));
+    }
+
+    #[test]
+    fn observer_sees_pushes_pops_and_emptying_in_order() {
+        let trace: Arc<std::sync::Mutex<Vec<String>>> = Arc::default();
+        let record = |prefix: &'static str| {
+            let trace = Arc::clone(&trace);
+            Box::new(move |task: &Task| trace.lock().unwrap().push(format!("{} {:?}", prefix, task.priority_level))) as TaskHook
+        };
+        let on_empty = Arc::clone(&trace);
+        let mut queue = PriorityQueue::with_observer(QueueObserver {
+            on_push: Some(record("push")),
+            on_pop: Some(record("pop")),
+            on_empty: Some(Box::new(move || on_empty.lock().unwrap().push("empty".into()))),
+        });
+
+        queue.push(task(Ok(0), PriorityLevel::Low));
+        queue.push(task(Ok(0), PriorityLevel::High));
+        assert!(queue.pop_simple().is_some());
+        assert!(queue.pop_simple().is_some());
+        assert!(queue.pop_simple().is_none());
+
+        assert_eq!(*trace.lock().unwrap(), ["push Low", "push High", "pop High", "pop Low", "empty"]);
+    }
+
+    #[test]
+    fn pop_if_leaves_the_queue_alone_when_the_next_task_does_not_match() {
+        let mut queue = PriorityQueue::new();
+        assert!(queue.pop_if(|_| true).is_none());
+
+        let mut tagged = task(Ok(0), PriorityLevel::Low);
+        tagged.tags.push("gpu".into());
+        let tagged_id = tagged.id;
+        let high = task(Ok(0), PriorityLevel::High);
+        let high_id = high.id;
+        queue.push(tagged);
+        queue.push(high);
+        let before = queue.contents();
+
+        // The tagged task is queued, but it is not next.
+        assert!(queue.pop_if(|task| task.tags.iter().any(|tag| tag == "gpu")).is_none());
+        assert_eq!(queue.contents(), before);
+
+        assert_eq!(queue.pop_if(|task| task.priority_level == PriorityLevel::High).map(|task| task.id), Some(high_id));
+        assert_eq!(queue.pop_if(|task| task.tags.iter().any(|tag| tag == "gpu")).map(|task| task.id), Some(tagged_id));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn task_position_counts_the_tasks_ahead() {
+        use PriorityLevel::{High, Low, Medium};
+        let mut queue = PriorityQueue::new();
+        assert_eq!(queue.task_position(Uuid::from_u128(1)), None);
+
+        let tasks: Vec<Task> = [Low, High, Medium, High, Low].into_iter().map(|level| task(Ok(0), level)).collect();
+        let ids: Vec<Uuid> = tasks.iter().map(|task| task.id).collect();
+        for task in tasks {
+            queue.push(task);
+        }
+        let positions: Vec<Option<usize>> = ids.iter().map(|id| queue.task_position(*id)).collect();
+        assert_eq!(positions, [Some(3), Some(0), Some(2), Some(1), Some(4)]);
+
+        assert!(queue.pop_simple().is_some());
+        assert_eq!(queue.task_position(ids[1]), None);
+        assert_eq!(queue.task_position(ids[3]), Some(0));
+        assert_eq!(queue.task_position(ids[4]), Some(3));
+        assert_eq!(queue.task_position(Uuid::from_u128(1)), None);
+    }
+
+    #[test]
+    fn split_at_priority_divides_at_each_level() {
+        use PriorityLevel::{High, Low, Medium};
+        let levels = [Medium, Low, High, Low, High, Medium];
+        let split = |level| {
+            let mut queue = PriorityQueue::new();
+            for priority_level in levels {
+                queue.push(task(Ok(0), priority_level));
+            }
+            let (urgent, rest) = queue.split_at_priority(level);
+            assert!(queue.is_empty());
+            let levels_of = |queue: &PriorityQueue| queue.iter().map(|task| task.priority_level).collect::<Vec<_>>();
+            (levels_of(&urgent), levels_of(&rest))
+        };
+
+        assert_eq!(split(High), (vec![High, High], vec![Medium, Medium, Low, Low]));
+        assert_eq!(split(Medium), (vec![High, High, Medium, Medium], vec![Low, Low]));
+        assert_eq!(split(Low), (vec![High, High, Medium, Medium, Low, Low], vec![]));
+    }
+
+    #[test]
+    fn split_queues_keep_their_order_for_later_pushes() {
+        let mut queue = PriorityQueue::new();
+        let first = task(Ok(0), PriorityLevel::Low);
+        let first_id = first.id;
+        queue.push(first);
+        let (urgent, mut rest) = queue.split_at_priority(PriorityLevel::Medium);
+        assert!(urgent.is_empty());
+
+        let later = task(Ok(0), PriorityLevel::Low);
+        let later_id = later.id;
+        rest.push(later);
+        assert_eq!(pop_ids(&mut rest), [first_id, later_id]);
+
+        let (urgent, rest) = PriorityQueue::new().split_at_priority(PriorityLevel::High);
+        assert!(urgent.is_empty() && rest.is_empty());
+    }
+
+    #[test]
+    fn pop_reports_what_is_left() {
+        use PriorityLevel::{High, Low, Medium};
+        let mut queue = PriorityQueue::new();
+        for level in [Low, High, Medium, High] {
+            queue.push(task(Ok(0), level));
+        }
+
+        let snapshots: Vec<QueueSnapshot> = core::iter::from_fn(|| queue.pop()).map(|(_, snapshot)| snapshot).collect();
+        let snapshot = |remaining, high_count, medium_count, low_count| QueueSnapshot { remaining, high_count, medium_count, low_count };
+        assert_eq!(snapshots, [snapshot(3, 1, 1, 1), snapshot(2, 0, 1, 1), snapshot(1, 0, 0, 1), snapshot(0, 0, 0, 0)]);
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn add_and_add_assign_merge_in_priority_order() {
+        use PriorityLevel::{High, Low, Medium};
+        let fill = |levels: &[PriorityLevel]| {
+            let mut queue = PriorityQueue::new();
+            for &level in levels {
+                queue.push(task(Ok(0), level));
+            }
+            queue
+        };
+        let levels_of = |queue: &PriorityQueue| queue.iter().map(|task| task.priority_level).collect::<Vec<_>>();
+
+        let merged = fill(&[Low, Medium]) + fill(&[High, Low]);
+        assert_eq!(levels_of(&merged), [High, Medium, Low, Low]);
+
+        let mut queue = fill(&[Medium]);
+        let low = fill(&[Low]);
+        let low_id = low.peek().unwrap().id;
+        queue += low;
+        queue += fill(&[High]);
+        queue += PriorityQueue::new();
+        assert_eq!(levels_of(&queue), [High, Medium, Low]);
+        assert_eq!(queue.task_position(low_id), Some(2));
+    }
+
+    #[test]
+    fn priority_of_follows_promote_and_demote() {
+        let mut queue = PriorityQueue::new();
+        let medium = task(Ok(0), PriorityLevel::Medium);
+        let low = task(Ok(0), PriorityLevel::Low);
+        let (medium_id, low_id) = (medium.id, low.id);
+        queue.push(medium);
+        queue.push(low);
+        assert_eq!(queue.priority_of(medium_id), Some(PriorityLevel::Medium));
+        assert_eq!(queue.priority_of(Uuid::from_u128(1)), None);
+
+        assert!(queue.promote(low_id));
+        assert!(queue.promote(low_id));
+        assert!(!queue.promote(low_id));
+        assert_eq!(queue.priority_of(low_id), Some(PriorityLevel::High));
+        assert_eq!(queue.task_position(low_id), Some(0));
+
+        assert!(queue.demote(medium_id));
+        assert!(!queue.demote(medium_id));
+        assert_eq!(queue.priority_of(medium_id), Some(PriorityLevel::Low));
+        assert!(!queue.promote(Uuid::from_u128(1)));
+
+        assert!(queue.pop_simple().is_some());
+        assert_eq!(queue.priority_of(low_id), None);
+        assert!(!queue.contains(low_id));
+        assert!(queue.contains(medium_id));
+    }
+
+    #[test]
+    fn handle_to_channel_sends_every_outcome() {
+        let mut queue = PriorityQueue::new();
+        let mut expected = hashbrown::HashMap::new();
+        for (index, level) in [PriorityLevel::Low, PriorityLevel::High, PriorityLevel::Medium].into_iter().enumerate() {
+            let result = if index == 1 { Err(TaskError::Failed("no".into())) } else { Ok(index as i32) };
+            let task = task(result.clone(), level);
+            expected.insert(task.id, result);
+            queue.push(task);
+        }
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let summary = queue.handle_to_channel(tx);
+
+        assert_eq!((summary.executed, summary.failed), (2, 1));
+        let received: hashbrown::HashMap<Uuid, Result<i32, TaskError>> = rx.into_iter().map(|outcome| (outcome.task_id, outcome.result)).collect();
+        assert_eq!(received, expected);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn handle_to_channel_runs_every_task_after_the_receiver_hangs_up() {
+        let mut queue = PriorityQueue::new();
+        for _ in 0..3 {
+            queue.push(task(Ok(0), PriorityLevel::Medium));
+        }
+        let (tx, rx) = std::sync::mpsc::channel();
+        drop(rx);
+        assert_eq!(queue.handle_to_channel(tx).executed, 3);
+    }
+
+    #[test]
+    fn handle_round_robin_deals_tasks_out_in_turn() {
+        let log = Arc::new(logger::WriterLogger::new(Vec::new()));
+        let mut queue = PriorityQueue::with_logger(log.clone());
+        for level in [PriorityLevel::Low, PriorityLevel::High, PriorityLevel::Medium, PriorityLevel::Low, PriorityLevel::High, PriorityLevel::Medium, PriorityLevel::Low] {
+            queue.push(task(Ok(0), level));
+        }
+        let pop_order: Vec<Uuid> = queue.iter().map(|task| task.id).collect();
+
+        let summary = queue.handle_round_robin(3);
+
+        assert_eq!(summary.executed, 7);
+        let contents = log.contents();
+        for (index, id) in pop_order.iter().enumerate() {
+            assert!(contents.contains(&format!("Worker {} executed task {} ", index % 3, id)), "{}", contents);
+        }
+        let per_worker: Vec<usize> = (0..3).map(|worker| contents.matches(&format!("Worker {} ", worker)).count()).collect();
+        assert_eq!(per_worker, [3, 2, 2]);
+    }
+
+    #[test]
+    fn handle_round_robin_with_no_workers_uses_one() {
+        let log = Arc::new(logger::WriterLogger::new(Vec::new()));
+        let mut queue = PriorityQueue::with_logger(log.clone());
+        queue.push(task(Ok(0), PriorityLevel::Medium));
+        queue.push(task(Err(TaskError::Skipped), PriorityLevel::Medium));
+
+        let summary = queue.handle_round_robin(0);
+
+        assert_eq!((summary.executed, summary.skipped), (1, 1));
+        assert_eq!(log.contents().matches("Worker 0 ").count(), 2);
+        assert_eq!(PriorityQueue::new().handle_round_robin(2), Summary::default());
+    }
+
+    #[test]
+    fn both_ends_of_the_queue_can_be_popped() {
+        use PriorityLevel::{High, Low, Medium};
+        let mut queue = PriorityQueue::new();
+        assert!(queue.peek_lowest().is_none());
+        assert!(queue.pop_lowest().is_none());
+        assert!(queue.pop_highest().is_none());
+
+        let tasks: Vec<Task> = [Medium, Low, High, Low].into_iter().map(|level| task(Ok(0), level)).collect();
+        let ids: Vec<Uuid> = tasks.iter().map(|task| task.id).collect();
+        for task in tasks {
+            queue.push(task);
+        }
+
+        // Of the two `Low` tasks, the one pushed last would run last.
+        assert_eq!(queue.peek_lowest().map(|task| task.id), Some(ids[3]));
+        assert_eq!(queue.pop_lowest().map(|task| task.id), Some(ids[3]));
+        assert_eq!(queue.pop_highest().map(|task| task.id), Some(ids[2]));
+        assert_eq!(queue.pop_lowest().map(|task| task.id), Some(ids[1]));
+        assert_eq!((queue.len(), queue.task_position(ids[0])), (1, Some(0)));
+        assert_eq!(queue.peek_lowest().map(|task| task.id), Some(ids[0]));
+        assert_eq!(queue.pop_lowest().map(|task| task.id), Some(ids[0]));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn handle_with_timeout_per_task_fails_only_the_slow_task() {
+        struct Sleeps(Duration);
+
+        impl TaskHandler for Sleeps {
+            fn execute(&self) -> Result<i32, TaskError> {
+                sleep(self.0);
+                Ok(0)
+            }
+        }
+
+        let mut queue = PriorityQueue::with_logger(Arc::new(logger::WriterLogger::new(Vec::new())));
+        queue.push(Task::new(Sleeps(Duration::from_secs(5)), PriorityLevel::High));
+        queue.push(task(Ok(1), PriorityLevel::Low));
+        let started = std::time::Instant::now();
+
+        let summary = queue.handle_with_timeout_per_task(Duration::from_millis(100));
+
+        assert_eq!((summary.executed, summary.failed), (1, 1));
+        assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn top_k_lists_the_next_tasks_without_popping_them() {
+        use PriorityLevel::{High, Low, Medium};
+        let mut queue = PriorityQueue::new();
+        assert!(queue.top_k(3).is_empty());
+
+        let levels = [Low, Medium, High, Low, Medium, Low, High, Medium, Low, Low];
+        for level in levels {
+            queue.push(task(Ok(0), level));
+        }
+        let expected: Vec<Uuid> = queue.iter().map(|task| task.id).collect();
+
+        let top: Vec<PriorityLevel> = queue.top_k(3).into_iter().map(|task| task.priority_level).collect();
+        assert_eq!(top, [High, High, Medium]);
+        assert_eq!(queue.top_k(0).len(), 0);
+        // Asking for more than are queued returns them all.
+        let all: Vec<Uuid> = queue.top_k(25).into_iter().map(|task| task.id).collect();
+        assert_eq!(all, expected);
+        assert_eq!(queue.len(), 10);
+    }
+
+    #[test]
+    fn handle_detached_runs_the_tasks_in_the_background() {
+        use core::sync::atomic::{AtomicBool, Ordering};
+
+        /// Waits until `self.0` is set.
+        struct WaitsFor(Arc<AtomicBool>);
+
+        impl TaskHandler for WaitsFor {
+            fn execute(&self) -> Result<i32, TaskError> {
+                while !self.0.load(Ordering::SeqCst) {
+                    sleep(Duration::from_millis(1));
+                }
+                Ok(0)
+            }
+        }
+
+        let release = Arc::new(AtomicBool::new(false));
+        let mut queue = PriorityQueue::with_logger(Arc::new(logger::WriterLogger::new(Vec::new())));
+        for _ in 0..5 {
+            queue.push(Task::new(WaitsFor(Arc::clone(&release)), PriorityLevel::Medium));
+        }
+
+        let handle = queue.handle_detached();
+        assert!(queue.is_empty());
+        queue.push(task(Ok(0), PriorityLevel::High));
+        assert_eq!(queue.len(), 1);
+        sleep(Duration::from_millis(20));
+        assert!(!handle.is_finished());
+
+        release.store(true, Ordering::SeqCst);
+        assert_eq!(handle.join().executed, 5);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn resolver_decides_the_priority_at_push_time() {
+        /// Makes tasks with an even id `High`, the rest `Low`.
+        struct EvenIdsFirst;
+
+        impl PriorityResolver for EvenIdsFirst {
+            fn resolve(&self, task: &Task) -> PriorityLevel {
+                if task.id.as_u128().is_multiple_of(2) { PriorityLevel::High } else { PriorityLevel::Low }
+            }
+        }
+
+        let mut queue = PriorityQueue::with_resolver(Arc::new(EvenIdsFirst));
+        for id in 1..=4 {
+            queue.push(Task::with_id(Uuid::from_u128(id), Box::new(Returns(Ok(0))), PriorityLevel::Medium));
+        }
+
+        assert_eq!(queue.priority_of(Uuid::from_u128(1)), Some(PriorityLevel::Low));
+        assert_eq!(queue.priority_of(Uuid::from_u128(2)), Some(PriorityLevel::High));
+        assert_eq!(pop_ids(&mut queue), [2, 4, 1, 3].map(Uuid::from_u128));
+    }
+
+    #[test]
+    fn receiver_is_drained_into_a_queue() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let sender = spawn(move || {
+            for level in [PriorityLevel::Low, PriorityLevel::High, PriorityLevel::Medium, PriorityLevel::Low, PriorityLevel::High] {
+                tx.send(task(Ok(0), level)).unwrap();
+                sleep(Duration::from_millis(1));
+            }
+        });
+
+        let queue = PriorityQueue::from(rx);
+        sender.join().unwrap();
+
+        assert_eq!(queue.len(), 5);
+        assert_eq!(queue.peek().map(|task| task.priority_level), Some(PriorityLevel::High));
+    }
+
+    #[test]
+    fn drain_channel_into_stops_when_the_channel_goes_quiet() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        for _ in 0..3 {
+            tx.send(task(Ok(0), PriorityLevel::Medium)).unwrap();
+        }
+        let mut queue = PriorityQueue::new();
+
+        // The sender is still alive, so only the timeout ends the drain.
+        assert_eq!(drain_channel_into(&rx, &mut queue, Duration::from_millis(20)), 3);
+        assert_eq!(queue.len(), 3);
+
+        tx.send(task(Ok(0), PriorityLevel::Medium)).unwrap();
+        drop(tx);
+        assert_eq!(drain_channel_into(&rx, &mut queue, Duration::from_secs(5)), 1);
+        assert_eq!(queue.len(), 4);
+    }
+
+    #[test]
+    fn indexing_reads_tasks_in_pop_order() {
+        use PriorityLevel::{High, Low, Medium};
+        let mut queue = PriorityQueue::new();
+        for level in [Low, High, Medium, Low, High] {
+            queue.push(task(Ok(0), level));
+        }
+        let expected: Vec<Uuid> = queue.iter().map(|task| task.id).collect();
+
+        let levels: Vec<PriorityLevel> = (0..queue.len()).map(|index| queue[index].priority_level).collect();
+        assert_eq!(levels, [High, High, Medium, Low, Low]);
+        assert_eq!((0..queue.len()).map(|index| queue[index].id).collect::<Vec<_>>(), expected);
+
+        // Takes effect at the next pop. The last task was pushed before the
+        // second `High` one, so it now runs ahead of it.
+        queue[4].priority_level = High;
+        assert_eq!(queue[4].id, expected[4]);
+        assert_eq!(queue.pop_simple().map(|task| task.id), Some(expected[0]));
+        assert_eq!(queue.pop_simple().map(|task| task.id), Some(expected[4]));
+        assert_eq!(queue.pop_simple().map(|task| task.id), Some(expected[1]));
+    }
+
+    #[test]
+    #[should_panic(expected = "index 2 out of range for queue of length 2")]
+    fn indexing_past_the_end_panics() {
+        let mut queue = PriorityQueue::new();
+        queue.push(task(Ok(0), PriorityLevel::Low));
+        queue.push(task(Ok(0), PriorityLevel::Low));
+        let _ = &queue[2];
+    }
+
+    #[test]
+    fn find_locates_tasks_by_tag_and_id() {
+        let tagged = |level, tag: &str| {
+            let mut task = task(Ok(0), level);
+            task.tags.push(tag.into());
+            task
+        };
+        let tasks = [tagged(PriorityLevel::Low, "billing"), tagged(PriorityLevel::High, "email"), tagged(PriorityLevel::Medium, "billing")];
+        let ids: Vec<Uuid> = tasks.iter().map(|task| task.id).collect();
+        let mut queue = PriorityQueue::new();
+        for task in tasks {
+            queue.push(task);
+        }
+        let has_tag = |tag: &'static str| move |task: &Task| task.tags.iter().any(|t| t == tag);
+
+        // The first match in pop order, not in push order.
+        assert_eq!(queue.find(has_tag("billing")).map(|task| task.id), Some(ids[2]));
+        assert_eq!(queue.find_all(has_tag("billing")).iter().map(|task| task.id).collect::<Vec<_>>(), [ids[2], ids[0]]);
+        assert!(queue.find(has_tag("sms")).is_none());
+        assert!(queue.find_all(has_tag("sms")).is_empty());
+
+        assert_eq!(queue.find_by_id(ids[1]).map(|task| task.priority_level), Some(PriorityLevel::High));
+        assert!(queue.find_by_id(Uuid::from_u128(1)).is_none());
+        assert!(queue.pop_simple().is_some());
+        assert!(queue.find_by_id(ids[1]).is_none());
+        assert_eq!(queue.find_by_id(ids[0]).map(|task| task.id), Some(ids[0]));
+    }
+
+    #[test]
+    fn swap_exchanges_the_tasks_of_two_queues() {
+        let fill = |n: usize| {
+            let mut queue = PriorityQueue::new();
+            for _ in 0..n {
+                queue.push(task(Ok(0), PriorityLevel::Medium));
+            }
+            queue
+        };
+        let mut a = fill(3);
+        let mut b = fill(1);
+        let a_ids: Vec<Uuid> = a.iter().map(|task| task.id).collect();
+
+        a.swap(&mut b);
+
+        assert_eq!((a.len(), b.len()), (1, 3));
+        assert_eq!(b.iter().map(|task| task.id).collect::<Vec<_>>(), a_ids);
+        assert_eq!(b.task_position(a_ids[2]), Some(2));
+        assert!(!a.contains(a_ids[0]));
+
+        // Pushed after the swap, so it queues up behind the tasks that arrived.
+        let later = task(Ok(0), PriorityLevel::Medium);
+        let later_id = later.id;
+        b.push(later);
+        assert_eq!(b.task_position(later_id), Some(3));
+    }
+
+    #[test]
+    fn take_moves_every_task_out() {
+        let mut queue = PriorityQueue::new();
+        for level in [PriorityLevel::Low, PriorityLevel::High] {
+            queue.push(task(Ok(0), level));
+        }
+        let expected: Vec<Uuid> = queue.iter().map(|task| task.id).collect();
+
+        let mut taken = queue.take();
+
+        assert!(queue.is_empty());
+        assert!(queue.pop().is_none());
+        assert_eq!(pop_ids(&mut taken), expected);
+        assert!(PriorityQueue::new().take().is_empty());
+    }
+
+    #[test]
+    fn execute_ordered_returns_outcomes_in_push_order() {
+        /// Sleeps for `self.1`, then returns `self.0`.
+        struct SlowReturns(i32, Duration);
+
+        impl TaskHandler for SlowReturns {
+            fn execute(&self) -> Result<i32, TaskError> {
+                sleep(self.1);
+                Ok(self.0)
+            }
+        }
+
+        use PriorityLevel::{High, Low, Medium};
+        let mut queue = PriorityQueue::new();
+        let mut ids = Vec::new();
+        for (value, level) in [Low, High, Medium, Low, High].into_iter().enumerate() {
+            // The first tasks pushed finish last.
+            let task = Task::new(SlowReturns(value as i32, Duration::from_millis(50 - 10 * value as u64)), level);
+            ids.push(task.id);
+            queue.push(task);
+        }
+
+        let outcomes = queue.execute_ordered();
+
+        assert_eq!(outcomes.iter().map(|outcome| outcome.task_id).collect::<Vec<_>>(), ids);
+        assert_eq!(outcomes.iter().map(|outcome| outcome.result.clone()).collect::<Vec<_>>(), [Ok(0), Ok(1), Ok(2), Ok(3), Ok(4)]);
+        assert!(queue.is_empty());
+        assert!(queue.execute_ordered().is_empty());
+    }
+
+    #[test]
+    fn tasks_that_would_outlive_their_deadline_are_skipped() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Claims to take a second; counts how often it actually runs.
+        struct Costly(Arc<AtomicUsize>);
+
+        impl TaskHandler for Costly {
+            fn execute(&self) -> Result<i32, TaskError> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(0)
+            }
+
+            fn cost_estimate(&self) -> Duration {
+                Duration::from_secs(1)
+            }
+        }
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let costly = |expires_in: Option<Duration>| {
+            let mut task = Task::new(Costly(Arc::clone(&runs)), PriorityLevel::Medium);
+            task.expires_at = expires_in.map(|expires_in| std::time::SystemTime::now() + expires_in);
+            task
+        };
+        let log = Arc::new(logger::WriterLogger::new(Vec::new()));
+        let mut queue = PriorityQueue::with_logger(log.clone());
+        let too_close = costly(Some(Duration::from_millis(500)));
+        let too_close_id = too_close.id;
+        queue.push(too_close);
+        queue.push(costly(Some(Duration::from_secs(60))));
+        queue.push(costly(None));
+
+        let summary = queue.handle_respecting_deadline_per_task();
+
+        assert_eq!((summary.executed, summary.skipped), (2, 1));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        assert!(log.contents().contains(&format!("Task {} (correlation {}) skipped", too_close_id, too_close_id)));
+    }
+
+    #[test]
+    fn subtracting_removes_the_tasks_queued_in_rhs() {
+        use PriorityLevel::{High, Low, Medium};
+        let levels = [Medium, Low, High, Low, High];
+        let fill = |tasks: Vec<Task>| {
+            let mut queue = PriorityQueue::new();
+            for task in tasks {
+                queue.push(task);
+            }
+            queue
+        };
+        let (tasks, twins) = twin_tasks(&levels);
+        let full = fill(tasks);
+        // Cancels ids 2 and 4, plus one that `full` never had.
+        let mut cancel: Vec<Task> = twins.into_iter().filter(|task| task.id.as_u128().is_multiple_of(2)).collect();
+        cancel.push(task(Ok(0), Low));
+        let cancel = fill(cancel);
+
+        let mut remaining = full - cancel;
+
+        assert_eq!(remaining.len(), 3);
+        assert_eq!(pop_ids(&mut remaining), [3, 5, 1].map(Uuid::from_u128));
+
+        let (tasks, twins) = twin_tasks(&levels);
+        let mut queue = fill(tasks);
+        queue -= PriorityQueue::new();
+        assert_eq!(queue.len(), 5);
+        queue -= fill(twins);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn rotate_sends_the_next_tasks_to_the_back_of_their_level() {
+        let mut queue = PriorityQueue::new();
+        let ids: Vec<Uuid> = (0..3)
+            .map(|_| {
+                let task = task(Ok(0), PriorityLevel::High);
+                let id = task.id;
+                queue.push(task);
+                id
+            })
+            .collect();
+
+        queue.rotate(1);
+        queue.rotate(1);
+        assert_eq!(pop_ids(&mut queue), [ids[2], ids[0], ids[1]]);
+    }
+
+    #[test]
+    fn rotate_keeps_priority_order_and_handles_any_count() {
+        use PriorityLevel::{High, Low};
+        let mut queue = PriorityQueue::new();
+        queue.rotate(3);
+        assert!(queue.is_empty());
+
+        for level in [High, Low, High, Low] {
+            queue.push(task(Ok(0), level));
+        }
+        let before: Vec<Uuid> = queue.iter().map(|task| task.id).collect();
+        queue.rotate(0);
+        assert_eq!(queue.iter().map(|task| task.id).collect::<Vec<_>>(), before);
+
+        // Two `High` tasks and one `Low`: each takes its place behind the
+        // rest of its level, and `High` stays ahead of `Low`.
+        queue.rotate(3);
+        assert_eq!(queue.iter().map(|task| task.id).collect::<Vec<_>>(), [before[0], before[1], before[3], before[2]]);
+
+        // Rotating every task, or more, keeps their order.
+        queue.rotate(10);
+        assert_eq!(pop_ids(&mut queue), [before[0], before[1], before[3], before[2]]);
+    }
+
+    #[test]
+    fn len_estimate_follows_len_through_the_wrappers() {
+        fn check<Q: TaskQueue>(mut queue: Q) {
+            assert_eq!(queue.len_estimate(), 0);
+            for level in [PriorityLevel::Low, PriorityLevel::High, PriorityLevel::Medium] {
+                queue.push(task(Ok(0), level));
+                assert_eq!(queue.len_estimate(), queue.len());
+            }
+            while queue.pop_simple().is_some() {
+                assert_eq!(queue.len_estimate(), queue.len());
+            }
+            assert_eq!(queue.len_estimate(), 0);
+        }
+
+        check(PriorityQueue::new());
+        check(vec_queue::VecQueue::new());
+        check(composite::CompositeQueue::new(composite::SelectionStrategy::RoundRobin, PriorityQueue::new(), 1).with_queue(PriorityQueue::new(), 1));
+        check(inspector::TaskQueueInspector::new(PriorityQueue::new()));
+        check(watermark::WatermarkQueue::new(PriorityQueue::new(), 1, 2, || {}, || {}));
+    }
+
+    #[test]
+    fn len_estimate_keeps_up_with_concurrent_pushes() {
+        let queue = Arc::new(std::sync::Mutex::new(PriorityQueue::new()));
+        let producers: Vec<_> = (0..4)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                spawn(move || {
+                    for _ in 0..100 {
+                        queue.lock().unwrap().push(task(Ok(0), PriorityLevel::Medium));
+                    }
+                })
+            })
+            .collect();
+        while !producers.iter().all(|producer| producer.is_finished()) {
+            let queue = queue.lock().unwrap();
+            assert!(queue.len_estimate().abs_diff(queue.len()) <= 2);
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert_eq!(queue.lock().unwrap().len_estimate(), 400);
+    }
+
+    #[test]
+    fn one_arc_handler_backs_several_tasks() {
+        let shared = Arc::new(HardProblem::new(3, 4).with_delay(Duration::ZERO));
+        let mut queue = PriorityQueue::new();
+        let first = Task::new(Arc::clone(&shared), PriorityLevel::High);
+        let second = Task::new(Arc::clone(&shared), PriorityLevel::Low);
+        let first_id = first.id;
+        queue.push(first);
+        queue.push(second);
+        assert_eq!(Arc::strong_count(&shared), 3);
+
+        // Reached through the `Arc`, the handler still names and exposes itself.
+        assert_eq!(queue.inspect_handler::<HardProblem<i32>>(first_id).map(HardProblem::num1), Some(&3));
+        assert_eq!(queue.peek().unwrap().handler.handler_type_name(), "HardProblem");
+        assert_eq!(queue.peek().unwrap().handler.cost_estimate(), shared.cost_estimate());
+
+        let results: Vec<Result<i32, TaskError>> = core::iter::from_fn(|| queue.pop_simple()).map(|task| task.handler.execute()).collect();
+        assert_eq!(results, [Ok(7), Ok(7)]);
+        assert_eq!(Arc::strong_count(&shared), 1);
+    }
+
+    #[test]
+    fn map_in_place_updates_every_task_and_reorders() {
+        use PriorityLevel::{High, Low, Medium};
+        let mut queue = PriorityQueue::new();
+        queue.map_in_place(|_| panic!("the queue is empty"));
+        let tasks: Vec<Task> = [Low, High, Medium].into_iter().map(|level| task(Ok(0), level)).collect();
+        let ids: Vec<Uuid> = tasks.iter().map(|task| task.id).collect();
+        for task in tasks {
+            queue.push(task);
+        }
+
+        queue.map_in_place(|task| task.tags.push("nightly".into()));
+        assert!(queue.iter().all(|task| task.tags == ["nightly"]));
+        assert_eq!(queue.len(), 3);
+
+        // Flip the priorities: the pop order, positions and snapshots follow.
+        queue.map_in_place(|task| {
+            task.priority_level = match task.priority_level {
+                High => Low,
+                Medium => Medium,
+                Low => High,
+            }
+        });
+        assert_eq!(queue.task_position(ids[0]), Some(0));
+        assert_eq!(queue.priority_of(ids[1]), Some(Low));
+        let (first, snapshot) = queue.pop().unwrap();
+        assert_eq!(first.id, ids[0]);
+        assert_eq!((snapshot.high_count, snapshot.medium_count, snapshot.low_count), (0, 1, 1));
+        assert_eq!(pop_ids(&mut queue), [ids[2], ids[1]]);
+    }
+
+    #[test]
+    fn inspect_handler_downcasts_queued_handlers() {
+        let mut queue = PriorityQueue::new();
+        let problem = Task::new(HardProblem::new(3, 4), PriorityLevel::Medium);
+        let opaque = task(Ok(0), PriorityLevel::High);
+        let (problem_id, opaque_id) = (problem.id, opaque.id);
+        queue.push(problem);
+        queue.push(opaque);
+
+        let handler = queue.inspect_handler::<HardProblem<i32>>(problem_id).unwrap();
+        assert_eq!((*handler.num1(), *handler.num2()), (3, 4));
+        assert!(queue.inspect_handler::<HardProblem<u8>>(problem_id).is_none());
+        assert!(queue.inspect_handler::<Returns>(opaque_id).is_none());
+        assert!(queue.inspect_handler::<HardProblem<i32>>(Uuid::from_u128(7)).is_none());
+
+        let popped = queue.pop_by_id(problem_id).unwrap();
+        assert!(queue.inspect_handler::<HardProblem<i32>>(popped.id).is_none());
+    }
+}
@@ -0,0 +1,144 @@
+//! Single-threaded counterparts of [`Task`](crate::Task) and
+//! [`TaskQueue`](crate::TaskQueue) whose handlers need not be `Send` or
+//! `Sync`.
+
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+
+use uuid::Uuid;
+
+use crate::{catch_panics, PriorityLevel, Summary, TaskError};
+
+pub trait LocalTaskHandler {
+    fn execute(&self) -> Result<i32, TaskError>;
+}
+
+pub struct LocalTask {
+    pub id: Uuid,
+    pub handler: Rc<dyn LocalTaskHandler>,
+    pub priority_level: PriorityLevel,
+}
+
+pub trait LocalTaskQueue {
+    fn push(&mut self, task: LocalTask);
+    fn pop(&mut self) -> Option<LocalTask>;
+    fn peek(&self) -> Option<&LocalTask>;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool;
+    fn handle(&mut self) -> Summary;
+}
+
+/// Pops the most urgent task first, and tasks of equal priority in the order
+/// they were pushed.
+#[derive(Default)]
+pub struct LocalPriorityQueue {
+    // Each task with the order it was pushed in, sorted so the task that
+    // runs next is last.
+    tasks: Vec<(u64, LocalTask)>,
+    next_sequence_number: u64,
+}
+
+impl LocalPriorityQueue {
+    pub fn new() -> Self {
+        LocalPriorityQueue::default()
+    }
+}
+
+impl LocalTaskQueue for LocalPriorityQueue {
+    fn push(&mut self, task: LocalTask) {
+        self.tasks.push((self.next_sequence_number, task));
+        self.next_sequence_number += 1;
+        self.tasks.sort_by_key(|(sequence_number, task)| {
+            Reverse((task.priority_level, *sequence_number))
+        });
+    }
+
+    fn pop(&mut self) -> Option<LocalTask> {
+        self.tasks.pop().map(|(_, task)| task)
+    }
+
+    fn peek(&self) -> Option<&LocalTask> {
+        self.tasks.last().map(|(_, task)| task)
+    }
+
+    fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Runs every task in priority order on the current thread.
+    fn handle(&mut self) -> Summary {
+        let mut summary = Summary::default();
+        while let Some(task) = self.pop() {
+            summary.record(&catch_panics(|| task.handler.execute()));
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+
+    /// Adds `amount` to a counter shared with the test, which is not `Send`.
+    struct AddTo {
+        total: Rc<Cell<i32>>,
+        amount: i32,
+    }
+
+    impl LocalTaskHandler for AddTo {
+        fn execute(&self) -> Result<i32, TaskError> {
+            self.total.set(self.total.get() + self.amount);
+            Ok(self.total.get())
+        }
+    }
+
+    fn add_to(total: &Rc<Cell<i32>>, amount: i32, priority_level: PriorityLevel) -> LocalTask {
+        LocalTask {
+            id: Uuid::from_u128(amount as u128),
+            handler: Rc::new(AddTo {
+                total: Rc::clone(total),
+                amount,
+            }),
+            priority_level,
+        }
+    }
+
+    #[test]
+    fn handlers_share_a_non_send_counter() {
+        let total = Rc::new(Cell::new(0));
+        let mut queue = LocalPriorityQueue::new();
+        for amount in 1..=4 {
+            queue.push(add_to(&total, amount, PriorityLevel::Medium));
+        }
+
+        let summary = queue.handle();
+
+        assert_eq!(summary.executed, 4);
+        assert_eq!(total.get(), 10);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn pops_by_priority_then_push_order() {
+        let total = Rc::new(Cell::new(0));
+        let mut queue = LocalPriorityQueue::new();
+        queue.push(add_to(&total, 1, PriorityLevel::Low));
+        queue.push(add_to(&total, 2, PriorityLevel::High));
+        queue.push(add_to(&total, 3, PriorityLevel::Low));
+        queue.push(add_to(&total, 4, PriorityLevel::High));
+
+        assert_eq!(queue.peek().map(|task| task.id), Some(Uuid::from_u128(2)));
+        let order: Vec<u128> = core::iter::from_fn(|| queue.pop())
+            .map(|task| task.id.as_u128())
+            .collect();
+        assert_eq!(order, [2, 4, 1, 3]);
+        assert_eq!(total.get(), 0);
+    }
+}
@@ -0,0 +1,114 @@
+use std::io::{self, Stderr, Stdout, Write};
+use std::sync::Mutex;
+
+/// Where a [`WorkerPool`](crate::worker::WorkerPool) sends its log lines.
+pub trait Logger: Send + Sync {
+    fn log(&self, line: &str);
+}
+
+/// Writes each log line, newline-terminated, to `W`. Write errors are ignored
+/// so that logging never fails a task.
+pub struct WriterLogger<W: Write + Send + Sync> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send + Sync> WriterLogger<W> {
+    pub fn new(writer: W) -> Self {
+        WriterLogger {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap()
+    }
+}
+
+impl WriterLogger<Stdout> {
+    pub fn stdout() -> Self {
+        WriterLogger::new(io::stdout())
+    }
+}
+
+impl WriterLogger<Stderr> {
+    pub fn stderr() -> Self {
+        WriterLogger::new(io::stderr())
+    }
+}
+
+impl WriterLogger<Vec<u8>> {
+    /// Everything logged so far, for asserting on in tests.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.writer.lock().unwrap()).into_owned()
+    }
+}
+
+impl<W: Write + Send + Sync> Logger for WriterLogger<W> {
+    fn log(&self, line: &str) {
+        let _ = writeln!(self.writer.lock().unwrap(), "{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn lines_are_newline_terminated() {
+        let logger = WriterLogger::new(Vec::new());
+        logger.log("first");
+        logger.log("second line");
+
+        assert_eq!(logger.contents(), "first\nsecond line\n");
+        assert_eq!(
+            logger.contents().lines().collect::<Vec<_>>(),
+            ["first", "second line"]
+        );
+        assert_eq!(logger.into_inner(), b"first\nsecond line\n");
+    }
+
+    #[test]
+    fn concurrent_lines_do_not_interleave() {
+        let logger = Arc::new(WriterLogger::new(Vec::new()));
+        let writers: Vec<_> = (0..4)
+            .map(|writer| {
+                let logger = Arc::clone(&logger);
+                thread::spawn(move || {
+                    for line in 0..50 {
+                        logger.log(&format!("writer {} line {}", writer, line));
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let contents = logger.contents();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 200);
+        assert!(lines
+            .iter()
+            .all(|line| line.starts_with("writer ") && line.split(' ').count() == 4));
+    }
+
+    #[test]
+    fn write_errors_are_ignored() {
+        struct Broken;
+
+        impl Write for Broken {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk full"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        WriterLogger::new(Broken).log("lost");
+    }
+}
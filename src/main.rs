@@ -1,118 +1,14 @@
 pub mod zero_knowledge;
-use std::fmt::{Debug, Display};
-use std::ops::{AddAssign};
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread::{sleep, spawn};
-use uuid::Uuid;
-
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
-enum PriorityLevel {
-    High,
-    Medium,
-    Low,
-}
-
-pub trait TaskHandler {
-    fn execute(&self) -> i32;
-}
-
-pub struct Task {
-    pub id: Uuid,
-    pub handler: Box<dyn TaskHandler + Send + Sync>,
-    pub priority_level: PriorityLevel,
-}
-
-pub trait TaskQueue {
-    fn push(&mut self, task: Task);
-    fn pop(&mut self) -> Option<Task>;
-    fn peek(&self) -> Option<&Task>;
-    fn len(&self) -> usize;
-    fn is_empty(&self) -> bool;
-    fn handle(&mut self);
-}
-
-pub struct PriorityQueue {
-    tasks: Vec<Task>,
-}
-
-impl TaskQueue for PriorityQueue {
-    fn push(&mut self, task: Task) {
-        self.tasks.push(task);
-        self.tasks.sort_by(|a, b| b.priority_level.cmp(&a.priority_level));
-    }
-
-    fn pop(&mut self) -> Option<Task> {
-        self.tasks.pop()
-    }
-
-    fn peek(&self) -> Option<&Task> {
-        self.tasks.first()
-    }
-
-    fn len(&self) -> usize {
-        self.tasks.len()
-    }
-
-    fn is_empty(&self) -> bool {
-        self.tasks.is_empty()
-    }
-
-    fn handle(&mut self) {
-        while let Some(task) = self.pop() {
-            let handler = task.handler;
-            let priority_level = task.priority_level;
-            spawn(move || {
-                let result = handler.execute();
-                println!("Task with priority {:?} executed with result: {}", priority_level, result);
-            });
-        }
-    }
-}
-
-pub struct HardProblem<T>
-{
-    num1: T,
-    num2: T,
-}
-
-impl<T> HardProblem<T>
-where T: AddAssign + Into<i32> + Clone + Display
-{
-    pub fn new(num1: T, num2: T) -> Self {
-        HardProblem { num1, num2 }
-    }
-
-    pub fn solve(&self) -> i32 {
-        let mut result = self.num1.clone();
-        result += self.num2.clone();
-        let int_result: i32 = result.into();
-        sleep(std::time::Duration::from_secs(1));
-        int_result
-    }
-}
-
-impl<T> TaskHandler for HardProblem<T>
-where T: AddAssign + Into<i32> + Clone + Display {
-    fn execute(&self) -> i32 {
-        self.solve()
-    }
-}
+use task_handler::{HardProblem, PriorityLevel, PriorityQueue, Task, TaskQueue};
 
 fn main() {
     let (sender, receiver) = mpsc::channel::<Task>();
-    let queue = Arc::new(Mutex::new(PriorityQueue { tasks: Vec::new() }));
+    let queue = Arc::new(Mutex::new(PriorityQueue::new()));
 
-    let task1 = Task {
-        id: Uuid::new_v4(),
-        handler: Box::new(HardProblem::new(3, 2)),
-        priority_level: PriorityLevel::High,
-    };
-
-    let task2 = Task {
-        id: Uuid::new_v4(),
-        handler: Box::new(HardProblem::new(5, 7)),
-        priority_level: PriorityLevel::Low,
-    };
+    let task1 = Task::new(HardProblem::new(3, 2), PriorityLevel::High);
+    let task2 = Task::new(HardProblem::new(5, 7), PriorityLevel::Low);
 
     {
         let mut queue = queue.lock().unwrap();
@@ -122,15 +18,12 @@ fn main() {
 
     let sender_thread = spawn(move || {
         for i in 0..10 {
-            let task = Task {
-                id: Uuid::new_v4(),
-                handler: Box::new(HardProblem::new(i as i32, (i + 1) as i32)),
-                priority_level: if i % 2 == 0 {
-                    PriorityLevel::High
-                } else {
-                    PriorityLevel::Low
-                },
+            let priority_level = if i % 2 == 0 {
+                PriorityLevel::High
+            } else {
+                PriorityLevel::Low
             };
+            let task = Task::new(HardProblem::new(i, i + 1), priority_level);
             sender.send(task).unwrap();
             sleep(std::time::Duration::from_secs(1));
         }
@@ -139,26 +32,33 @@ fn main() {
     let queue_clone = Arc::clone(&queue);
     let task_handler_thread = spawn(move || {
         let mut queue = queue_clone.lock().unwrap();
-        queue.handle();
+        let summary = queue.handle();
+        println!("Handled queued tasks: {:?}", summary);
     });
 
     let receiver_thread = spawn(move || {
         while let Ok(task) = receiver.recv() {
-            let task_result = task.handler.execute();
+            let task_result = match task.handler.execute() {
+                Ok(task_result) => task_result,
+                Err(err) => {
+                    println!("Received task with ID: {} failed: {}", task.id, err);
+                    continue;
+                }
+            };
             println!("Received task with ID: {} produced result: {}", task.id, task_result);
 
             println!("Adding new task based on result: {}", task_result);
-            let medium_priority = Task {
-                id: Uuid::new_v4(),
-                handler: Box::new(HardProblem::new(task_result, task_result + 1)),
-                priority_level: PriorityLevel::Medium,
-            };
+            let mut medium_priority = Task::new(
+                HardProblem::new(task_result, task_result + 1),
+                PriorityLevel::Medium,
+            );
+            medium_priority.correlation_id = Some(task.child_correlation_id());
             
-            let high_priority = Task {
-                id: Uuid::new_v4(),
-                handler: Box::new(HardProblem::new(task_result, task_result + 1)),
-                priority_level: PriorityLevel::Low,
-            };
+            let mut high_priority = Task::new(
+                HardProblem::new(task_result, task_result + 1),
+                PriorityLevel::Low,
+            );
+            high_priority.correlation_id = Some(task.child_correlation_id());
 
             let mut queue = queue.lock().unwrap();
             queue.push(medium_priority);
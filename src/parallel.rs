@@ -0,0 +1,35 @@
+use alloc::vec::Vec;
+
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator, Rev,
+};
+
+use crate::{catch_panics, PriorityQueue, Task, TaskError};
+
+impl IntoParallelIterator for PriorityQueue {
+    type Item = Task;
+    type Iter = Rev<rayon::vec::IntoIter<Task>>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.tasks.into_par_iter().rev()
+    }
+}
+
+impl<'a> IntoParallelIterator for &'a PriorityQueue {
+    type Item = &'a Task;
+    type Iter = Rev<rayon::slice::Iter<'a, Task>>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.tasks.par_iter().rev()
+    }
+}
+
+impl PriorityQueue {
+    /// Executes every task on the rayon thread pool. Results are returned in
+    /// priority order, highest first.
+    pub fn execute_par(self) -> Vec<Result<i32, TaskError>> {
+        self.into_par_iter()
+            .map(|task| catch_panics(|| task.handler.execute()))
+            .collect()
+    }
+}
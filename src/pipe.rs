@@ -0,0 +1,86 @@
+use core::ops::BitOr;
+
+use crate::{HardProblem, TaskError, TaskHandler};
+
+/// A handler that can be rebuilt around the output of the previous stage in
+/// a pipeline. `self` acts as a template for everything but the input.
+pub trait FromI32 {
+    fn with_input(&self, input: i32) -> Self;
+}
+
+/// Feeds the first handler's result into the second, built with
+/// [`FromI32`]. A failing first stage skips the second.
+///
+/// ```
+/// use task_handler::{HardProblem, Operation, TaskHandler};
+/// use std::time::Duration;
+///
+/// let add = HardProblem::new(1, 2).with_delay(Duration::ZERO);
+/// let double = HardProblem::new(0, 2).with_operation(Operation::Mul).with_delay(Duration::ZERO);
+/// let minus_one = HardProblem::new(0, 1).with_operation(Operation::Sub).with_delay(Duration::ZERO);
+///
+/// assert_eq!((add | double | minus_one).execute(), Ok(5));
+/// ```
+pub struct PipedHandler<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> PipedHandler<A, B>
+where
+    A: TaskHandler,
+    B: TaskHandler + FromI32,
+{
+    pub fn new(first: A, second: B) -> Self {
+        PipedHandler { first, second }
+    }
+}
+
+impl<A, B> TaskHandler for PipedHandler<A, B>
+where
+    A: TaskHandler,
+    B: TaskHandler + FromI32,
+{
+    fn execute(&self) -> Result<i32, TaskError> {
+        let input = self.first.execute()?;
+        self.second.with_input(input).execute()
+    }
+}
+
+impl<A, B, C> BitOr<C> for PipedHandler<A, B>
+where
+    A: TaskHandler,
+    B: TaskHandler + FromI32,
+    C: TaskHandler + FromI32,
+{
+    type Output = PipedHandler<Self, C>;
+
+    fn bitor(self, next: C) -> Self::Output {
+        PipedHandler::new(self, next)
+    }
+}
+
+/// Replaces `num1` with the input, keeping `num2`, the operation and the
+/// delay.
+impl FromI32 for HardProblem<i32> {
+    fn with_input(&self, input: i32) -> Self {
+        HardProblem {
+            num1: input,
+            num2: self.num2,
+            op: self.op,
+            delay: self.delay,
+        }
+    }
+}
+
+impl<T, C> BitOr<C> for HardProblem<T>
+where
+    HardProblem<T>: TaskHandler,
+    C: TaskHandler + FromI32,
+{
+    type Output = PipedHandler<Self, C>;
+
+    fn bitor(self, next: C) -> Self::Output {
+        PipedHandler::new(self, next)
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::PriorityLevel;
+
+/// Source of the current time, so rate limiting can be driven by a
+/// [`SimulatedClock`] instead of waiting on the wall clock.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when [`SimulatedClock::advance`] is called.
+#[derive(Debug)]
+pub struct SimulatedClock {
+    now: Mutex<Instant>,
+}
+
+impl Default for SimulatedClock {
+    fn default() -> Self {
+        SimulatedClock {
+            now: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl SimulatedClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// Allows at most `max_tasks` acquisitions in any sliding `window`.
+pub struct WindowedRateLimiter {
+    window: Duration,
+    max_tasks: u32,
+    history: Mutex<VecDeque<Instant>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl WindowedRateLimiter {
+    pub fn new(window: Duration, max_tasks: u32) -> Self {
+        WindowedRateLimiter {
+            window,
+            max_tasks,
+            history: Mutex::new(VecDeque::new()),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Records an acquisition and returns `true` if fewer than `max_tasks`
+    /// were made in the last `window`, otherwise returns `false` and records
+    /// nothing.
+    pub fn try_acquire(&self) -> bool {
+        let now = self.clock.now();
+        let mut history = self.history.lock().unwrap();
+        while history
+            .front()
+            .is_some_and(|acquired| now.duration_since(*acquired) >= self.window)
+        {
+            history.pop_front();
+        }
+
+        if history.len() >= self.max_tasks as usize {
+            return false;
+        }
+        history.push_back(now);
+        true
+    }
+}
+
+/// Holds up to a second's worth of tokens, but always at least one; starts
+/// full.
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(rate: f64, now: Instant) -> Self {
+        TokenBucket {
+            rate,
+            tokens: rate.max(1.0),
+            refilled_at: now,
+        }
+    }
+
+    pub(crate) fn has_token(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= 1.0
+    }
+
+    /// Call only after `has_token` returned `true`.
+    pub(crate) fn take_token(&mut self) {
+        self.tokens -= 1.0;
+    }
+
+    fn capacity(&self) -> f64 {
+        self.rate.max(1.0)
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity());
+        self.refilled_at = now;
+    }
+}
+
+/// Throttles each priority level to its own rate, in tasks per second, with a
+/// token bucket per level. Levels without a limit are never throttled.
+pub struct PriorityRateLimiter {
+    buckets: Mutex<HashMap<PriorityLevel, TokenBucket>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl PriorityRateLimiter {
+    pub fn new(limits: HashMap<PriorityLevel, f64>) -> Self {
+        PriorityRateLimiter::with_clock(limits, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(limits: HashMap<PriorityLevel, f64>, clock: Arc<dyn Clock>) -> Self {
+        let now = clock.now();
+        let buckets = limits
+            .into_iter()
+            .map(|(priority_level, rate)| (priority_level, TokenBucket::new(rate, now)))
+            .collect();
+        PriorityRateLimiter {
+            buckets: Mutex::new(buckets),
+            clock,
+        }
+    }
+
+    /// Takes a token for `priority_level` if one is available.
+    pub fn try_acquire(&self, priority_level: PriorityLevel) -> bool {
+        self.wait_time(priority_level).is_none()
+    }
+
+    /// Blocks until a token for `priority_level` is available and takes it.
+    pub fn acquire(&self, priority_level: PriorityLevel) {
+        while let Some(wait) = self.wait_time(priority_level) {
+            thread::sleep(wait);
+        }
+    }
+
+    /// Takes a token and returns `None`, or returns how long until the next
+    /// token is due.
+    fn wait_time(&self, priority_level: PriorityLevel) -> Option<Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.get_mut(&priority_level)?;
+        if bucket.has_token(self.clock.now()) {
+            bucket.take_token();
+            return None;
+        }
+        if bucket.rate <= 0.0 {
+            return Some(Duration::from_secs(1));
+        }
+        Some(Duration::from_secs_f64((1.0 - bucket.tokens) / bucket.rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logger::WriterLogger;
+    use crate::worker::WorkerPool;
+    use crate::{HardProblem, Task, TaskError, TaskHandler};
+
+    #[test]
+    fn windowed_limiter_waits_for_the_window_to_slide() {
+        let clock = Arc::new(SimulatedClock::new());
+        let limiter = WindowedRateLimiter::new(Duration::from_secs(1), 5).with_clock(clock.clone());
+        for _ in 0..5 {
+            assert!(limiter.try_acquire());
+            clock.advance(Duration::from_millis(100));
+        }
+
+        assert!(!limiter.try_acquire());
+        clock.advance(Duration::from_millis(499));
+        assert!(!limiter.try_acquire());
+        // The first acquisition, at 0ms, has now left the window.
+        clock.advance(Duration::from_millis(1));
+        assert!(limiter.try_acquire());
+        assert!(!limiter.try_acquire());
+    }
+
+    #[test]
+    fn pool_holds_tasks_back_until_the_window_slides() {
+        let window = Duration::from_millis(200);
+        let pool = WorkerPool::new(2)
+            .with_rate_limiter(Arc::new(WindowedRateLimiter::new(window, 5)))
+            .with_logger(Arc::new(WriterLogger::new(Vec::new())));
+        for _ in 0..6 {
+            pool.submit(HardProblem::new(1, 2).with_delay(Duration::ZERO).into());
+        }
+
+        let started = Instant::now();
+        let summary = pool.run();
+
+        assert_eq!(summary.executed, 6);
+        assert!(started.elapsed() >= window);
+    }
+
+    #[test]
+    fn priority_limiter_only_throttles_limited_levels() {
+        let clock = Arc::new(SimulatedClock::new());
+        let limits = HashMap::from([(PriorityLevel::Low, 2.0)]);
+        let limiter = PriorityRateLimiter::with_clock(limits, clock.clone());
+
+        assert!(limiter.try_acquire(PriorityLevel::Low));
+        assert!(limiter.try_acquire(PriorityLevel::Low));
+        assert!(!limiter.try_acquire(PriorityLevel::Low));
+        assert!((0..100).all(|_| limiter.try_acquire(PriorityLevel::High)));
+
+        clock.advance(Duration::from_millis(500));
+        assert!(limiter.try_acquire(PriorityLevel::Low));
+        assert!(!limiter.try_acquire(PriorityLevel::Low));
+    }
+
+    #[test]
+    fn high_task_overtakes_a_throttled_low_flood() {
+        /// Records when it ran.
+        struct Stamp(Arc<Mutex<Option<Instant>>>);
+
+        impl TaskHandler for Stamp {
+            fn execute(&self) -> Result<i32, TaskError> {
+                *self.0.lock().unwrap() = Some(Instant::now());
+                Ok(0)
+            }
+        }
+
+        let limits = HashMap::from([(PriorityLevel::Low, 20.0)]);
+        let pool = WorkerPool::new(1)
+            .with_priority_rate_limiter(limits)
+            .with_logger(Arc::new(WriterLogger::new(Vec::new())));
+        for _ in 0..25 {
+            pool.submit(HardProblem::new(1, 2).with_delay(Duration::ZERO).into());
+        }
+
+        let ran_at = Arc::new(Mutex::new(None));
+        let started = Instant::now();
+        let (summary, submitted_at) = thread::scope(|scope| {
+            let run = scope.spawn(|| pool.run());
+            // Well after the first burst of 20, so the rest are throttled.
+            thread::sleep(Duration::from_millis(30));
+            let submitted_at = Instant::now();
+            pool.submit(Task::new(Stamp(Arc::clone(&ran_at)), PriorityLevel::High));
+            (run.join().unwrap(), submitted_at)
+        });
+
+        assert_eq!(summary.executed, 26);
+        let ran_at = ran_at.lock().unwrap().unwrap();
+        assert!(ran_at.duration_since(submitted_at) < Duration::from_millis(50));
+        // The last 5 low tasks needed 250ms of refills.
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+}
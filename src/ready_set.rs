@@ -0,0 +1,110 @@
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::logger::{Logger, WriterLogger};
+use crate::worker::IDLE_POLL_INTERVAL;
+use crate::{Task, TaskQueue};
+
+/// Forwards tasks from several named channels into one queue, pushing each
+/// task as soon as it is seen on any channel.
+pub struct ReadySet {
+    channels: Vec<(String, Receiver<Task>)>,
+    queue: Arc<Mutex<dyn TaskQueue + Send>>,
+    logger: Arc<dyn Logger>,
+}
+
+impl ReadySet {
+    pub fn new(queue: Arc<Mutex<dyn TaskQueue + Send>>) -> Self {
+        ReadySet {
+            channels: Vec::new(),
+            queue,
+            logger: Arc::new(WriterLogger::stdout()),
+        }
+    }
+
+    /// Reports disconnected channels to `logger` instead of stdout.
+    pub fn with_logger(mut self, logger: Arc<dyn Logger>) -> Self {
+        self.logger = logger;
+        self
+    }
+
+    pub fn add_channel(&mut self, name: &str, rx: Receiver<Task>) {
+        self.channels.push((name.to_string(), rx));
+    }
+
+    /// Polls every channel in turn until all senders have hung up. A channel
+    /// is dropped from the set once it disconnects.
+    pub fn run(&mut self) {
+        while !self.channels.is_empty() {
+            let mut forwarded = false;
+            let mut index = 0;
+            while index < self.channels.len() {
+                match self.channels[index].1.try_recv() {
+                    Ok(task) => {
+                        self.queue.lock().unwrap().push(task);
+                        forwarded = true;
+                        index += 1;
+                    }
+                    Err(TryRecvError::Empty) => index += 1,
+                    Err(TryRecvError::Disconnected) => {
+                        let (name, _) = self.channels.remove(index);
+                        self.logger.log(&format!("Channel {} disconnected", name));
+                    }
+                }
+            }
+            if !forwarded {
+                thread::sleep(IDLE_POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{HardProblem, PriorityLevel, PriorityQueue};
+
+    #[test]
+    fn tasks_from_both_channels_reach_the_queue() {
+        let queue = Arc::new(Mutex::new(PriorityQueue::new()));
+        let logger = Arc::new(WriterLogger::new(Vec::new()));
+        let mut ready_set = ReadySet::new(queue.clone()).with_logger(logger.clone());
+        let mut sent = Vec::new();
+        let mut senders = Vec::new();
+        for name in ["api", "cron"] {
+            let (tx, rx) = mpsc::channel();
+            ready_set.add_channel(name, rx);
+            let tasks: Vec<Task> = (0..3)
+                .map(|_| (HardProblem::new(1, 2), PriorityLevel::Medium).into())
+                .collect();
+            sent.push(tasks.iter().map(|task| task.id).collect::<Vec<_>>());
+            senders.push(thread::spawn(move || {
+                for task in tasks {
+                    tx.send(task).unwrap();
+                    thread::sleep(Duration::from_millis(5));
+                }
+            }));
+        }
+
+        ready_set.run();
+
+        senders
+            .into_iter()
+            .for_each(|sender| sender.join().unwrap());
+        let queue = queue.lock().unwrap();
+        assert_eq!(queue.len(), 6);
+        // Equal priorities pop in push order, so this is the arrival order.
+        let arrived: Vec<_> = queue.iter().map(|task| task.id).collect();
+        for ids in &sent {
+            let from_channel: Vec<_> = arrived.iter().filter(|id| ids.contains(id)).collect();
+            assert_eq!(from_channel, ids.iter().collect::<Vec<_>>());
+        }
+        let log = logger.contents();
+        assert!(log.contains("Channel api disconnected"));
+        assert!(log.contains("Channel cron disconnected"));
+    }
+}
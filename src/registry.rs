@@ -0,0 +1,303 @@
+//! Named handler factories, optionally populated by dynamically loaded
+//! plugins.
+//!
+//! A plugin is a `cdylib` built against the same version of this crate
+//! that exports:
+//!
+//! ```ignore
+//! #[no_mangle]
+//! pub extern "C" fn register_handlers(registry: *mut HandlerRegistry) {
+//!     let registry = unsafe { &mut *registry };
+//!     registry.register("my_handler", |args| Box::new(MyHandler::from(args)));
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use libloading::Library;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{PriorityLevel, Task, TaskHandler};
+
+pub type HandlerFactory = Box<dyn Fn(Value) -> Box<dyn TaskHandler + Send + Sync> + Send + Sync>;
+
+/// Signature of the entry point every plugin must export.
+pub type RegisterHandlers = unsafe extern "C" fn(registry: *mut HandlerRegistry);
+
+pub const REGISTER_HANDLERS_SYMBOL: &[u8] = b"register_handlers";
+
+#[derive(Debug)]
+pub enum RegistryError {
+    UnknownHandler(String),
+    Plugin(libloading::Error),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::UnknownHandler(name) => write!(f, "no handler registered as {}", name),
+            RegistryError::Plugin(err) => write!(f, "failed to load plugin: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+#[derive(Default)]
+pub struct HandlerRegistry {
+    map: HashMap<String, HandlerFactory>,
+    // Declared after `map` so factories are dropped before the code they
+    // point into is unloaded.
+    plugins: Vec<Library>,
+}
+
+impl HandlerRegistry {
+    pub fn new() -> Self {
+        HandlerRegistry::default()
+    }
+
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(Value) -> Box<dyn TaskHandler + Send + Sync> + Send + Sync + 'static,
+    {
+        self.map.insert(name.to_string(), Box::new(factory));
+    }
+
+    pub fn build(
+        &self,
+        name: &str,
+        args: Value,
+    ) -> Result<Box<dyn TaskHandler + Send + Sync>, RegistryError> {
+        let factory = self
+            .map
+            .get(name)
+            .ok_or_else(|| RegistryError::UnknownHandler(name.to_string()))?;
+        Ok(factory(args))
+    }
+
+    /// Loads the library at `path` and lets it register its handlers through
+    /// its `register_handlers` entry point.
+    ///
+    /// # Safety
+    ///
+    /// Loading a library runs its initialisation code, and the entry point is
+    /// trusted to have the [`RegisterHandlers`] signature. The plugin must be
+    /// built with the same compiler and the same version of this crate, since
+    /// the registry is passed across the boundary as a Rust type.
+    pub unsafe fn load_plugin(&mut self, path: &Path) -> Result<(), RegistryError> {
+        let library = Library::new(path).map_err(RegistryError::Plugin)?;
+        let register = library
+            .get::<RegisterHandlers>(REGISTER_HANDLERS_SYMBOL)
+            .map_err(RegistryError::Plugin)?;
+        register(self);
+        self.plugins.push(library);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum TaskDeserializationError {
+    MissingField(&'static str),
+    InvalidField(&'static str),
+    UnknownHandler(String),
+}
+
+impl fmt::Display for TaskDeserializationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskDeserializationError::MissingField(field) => write!(f, "missing field {}", field),
+            TaskDeserializationError::InvalidField(field) => {
+                write!(f, "invalid value for field {}", field)
+            }
+            TaskDeserializationError::UnknownHandler(name) => {
+                write!(f, "no handler registered as {}", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TaskDeserializationError {}
+
+/// Builds a task from a JSON description such as
+///
+/// ```json
+/// {
+///     "id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+///     "priority": "high",
+///     "handler_type": "hard_problem",
+///     "args": { "num1": 3, "num2": 2 },
+///     "tags": ["billing"],
+///     "expires_at": 1767225600
+/// }
+/// ```
+///
+/// `id`, `priority` and `handler_type` are required. `args` is passed to the
+/// registered factory as is (`null` if absent), and `expires_at` is in
+/// seconds since the Unix epoch.
+impl TryFrom<(Value, &HandlerRegistry)> for Task {
+    type Error = TaskDeserializationError;
+
+    fn try_from((mut value, registry): (Value, &HandlerRegistry)) -> Result<Self, Self::Error> {
+        let field = |value: &Value, name: &'static str| {
+            value
+                .get(name)
+                .and_then(Value::as_str)
+                .map(str::to_owned)
+                .ok_or(TaskDeserializationError::MissingField(name))
+        };
+
+        let id = field(&value, "id")?
+            .parse::<Uuid>()
+            .map_err(|_| TaskDeserializationError::InvalidField("id"))?;
+        let priority_level = field(&value, "priority")?
+            .parse::<PriorityLevel>()
+            .map_err(|_| TaskDeserializationError::InvalidField("priority"))?;
+        let handler_type = field(&value, "handler_type")?;
+
+        let tags = match value.get("tags") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(tags)) => tags
+                .iter()
+                .map(|tag| tag.as_str().map(str::to_owned))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(TaskDeserializationError::InvalidField("tags"))?,
+            Some(_) => return Err(TaskDeserializationError::InvalidField("tags")),
+        };
+        let expires_at = match value.get("expires_at") {
+            None | Some(Value::Null) => None,
+            Some(secs) => {
+                let expires_at = secs
+                    .as_u64()
+                    .and_then(|secs| UNIX_EPOCH.checked_add(Duration::from_secs(secs)))
+                    .ok_or(TaskDeserializationError::InvalidField("expires_at"))?;
+                Some(expires_at)
+            }
+        };
+
+        let args = value
+            .get_mut("args")
+            .map(Value::take)
+            .unwrap_or(Value::Null);
+        let handler = registry
+            .build(&handler_type, args)
+            .map_err(|_| TaskDeserializationError::UnknownHandler(handler_type))?;
+
+        let mut task = Task::with_id(id, handler, priority_level);
+        task.tags = tags;
+        task.expires_at = expires_at;
+        Ok(task)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::HardProblem;
+
+    fn registry() -> HandlerRegistry {
+        let mut registry = HandlerRegistry::new();
+        registry.register("hard_problem", |args| {
+            let arg = |name| args.get(name).and_then(Value::as_i64).unwrap_or(0) as i32;
+            Box::new(HardProblem::new(arg("num1"), arg("num2")).with_delay(Duration::ZERO))
+        });
+        registry
+    }
+
+    fn description() -> Value {
+        json!({
+            "id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "priority": "HIGH",
+            "handler_type": "hard_problem",
+            "args": { "num1": 3, "num2": 2 },
+            "tags": ["billing"],
+            "expires_at": 1767225600
+        })
+    }
+
+    #[test]
+    fn builds_the_task_through_the_registered_factory() {
+        let registry = registry();
+        let task = Task::try_from((description(), &registry)).unwrap();
+
+        assert_eq!(task.id.to_string(), "67e55044-10b1-426f-9247-bb680e5fe0c8");
+        assert_eq!(task.priority_level, PriorityLevel::High);
+        assert_eq!(task.tags, ["billing"]);
+        assert_eq!(
+            task.expires_at,
+            Some(UNIX_EPOCH + Duration::from_secs(1767225600))
+        );
+        assert_eq!(task.handler.execute(), Ok(5));
+    }
+
+    #[test]
+    fn unknown_handler_is_rejected() {
+        let registry = registry();
+        let mut value = description();
+        value["handler_type"] = json!("missing");
+
+        let err = Task::try_from((value, &registry))
+            .map(|task| task.id)
+            .unwrap_err();
+        assert!(
+            matches!(&err, TaskDeserializationError::UnknownHandler(name) if name == "missing"),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn malformed_fields_are_rejected() {
+        let registry = registry();
+        let with = |field: &str, replacement: Value| {
+            let mut value = description();
+            value[field] = replacement;
+            Task::try_from((value, &registry)).map(|task| task.id)
+        };
+
+        assert!(matches!(
+            with("id", json!("not-a-uuid")),
+            Err(TaskDeserializationError::InvalidField("id"))
+        ));
+        assert!(matches!(
+            with("priority", json!("urgent")),
+            Err(TaskDeserializationError::InvalidField("priority"))
+        ));
+        assert!(matches!(
+            with("handler_type", json!(7)),
+            Err(TaskDeserializationError::MissingField("handler_type"))
+        ));
+        assert!(matches!(
+            with("tags", json!(["billing", 1])),
+            Err(TaskDeserializationError::InvalidField("tags"))
+        ));
+        assert!(matches!(
+            with("expires_at", json!(-1)),
+            Err(TaskDeserializationError::InvalidField("expires_at"))
+        ));
+        assert!(matches!(
+            Task::try_from((json!([1, 2]), &registry)).map(|task| task.id),
+            Err(TaskDeserializationError::MissingField("id"))
+        ));
+    }
+
+    #[test]
+    fn optional_fields_may_be_left_out() {
+        let registry = registry();
+        let value = json!({
+            "id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "priority": "3",
+            "handler_type": "hard_problem"
+        });
+
+        let task = Task::try_from((value, &registry)).unwrap();
+        assert_eq!(task.priority_level, PriorityLevel::Low);
+        assert!(task.tags.is_empty());
+        assert_eq!(task.expires_at, None);
+        assert_eq!(task.handler.execute(), Ok(0));
+    }
+}
@@ -0,0 +1,151 @@
+//! Runs handlers in a child process, so a handler that panics, aborts or
+//! corrupts memory cannot take the worker down with it.
+//!
+//! The child is a separate binary whose `main` hands its own handler
+//! decoding to [`sandbox_main`]:
+//!
+//! ```ignore
+//! fn run_sandbox(handler_bytes: &[u8]) -> i32 {
+//!     let handler = MyHandler::decode(handler_bytes);
+//!     handler.execute().unwrap_or(1)
+//! }
+//!
+//! fn main() {
+//!     task_handler::sandbox::sandbox_main(run_sandbox);
+//! }
+//! ```
+
+use std::any::Any;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
+
+use crate::{TaskError, TaskHandler};
+
+/// Exit code [`sandbox_main`] uses when the handler panics. A sandboxed
+/// handler must not return it as a result.
+pub const SANDBOX_PANIC_EXIT_CODE: i32 = 101;
+
+/// Starts `binary`, writes `serialized_handler` to its stdin and returns its
+/// exit code. A child that panics fails the task with
+/// [`TaskError::HandlerPanic`] carrying its stderr; one that cannot be
+/// started or is killed by a signal fails it with [`TaskError::Failed`].
+pub struct SandboxedTask {
+    serialized_handler: Vec<u8>,
+    binary: PathBuf,
+}
+
+impl SandboxedTask {
+    pub fn new(binary: impl Into<PathBuf>, serialized_handler: Vec<u8>) -> Self {
+        SandboxedTask {
+            serialized_handler,
+            binary: binary.into(),
+        }
+    }
+}
+
+impl TaskHandler for SandboxedTask {
+    fn execute(&self) -> Result<i32, TaskError> {
+        let spawn_failed = |err: io::Error| {
+            TaskError::Failed(format!("failed to run {}: {}", self.binary.display(), err))
+        };
+        let mut child = Command::new(&self.binary)
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(spawn_failed)?;
+        let stdin = child.stdin.take();
+        // Written from another thread while this one drains stderr, so a
+        // child that fills the stderr pipe before reading all of its input
+        // cannot block both sides.
+        let output = std::thread::scope(|scope| {
+            if let Some(mut stdin) = stdin {
+                // A child that exits without reading its input closes the
+                // pipe; its exit status says more than the write error would.
+                scope.spawn(move || {
+                    let _ = stdin.write_all(&self.serialized_handler);
+                });
+            }
+            child.wait_with_output()
+        })
+        .map_err(spawn_failed)?;
+
+        match output.status.code() {
+            Some(SANDBOX_PANIC_EXIT_CODE) => Err(TaskError::HandlerPanic(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            )),
+            Some(code) => Ok(code),
+            None => Err(TaskError::Failed(format!(
+                "{} terminated by signal",
+                self.binary.display()
+            ))),
+        }
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// Entry point for a sandbox binary: reads the serialized handler from
+/// stdin, passes it to `run_sandbox` and exits with the code it returns, or
+/// with [`SANDBOX_PANIC_EXIT_CODE`] if it panics.
+pub fn sandbox_main(run_sandbox: fn(&[u8]) -> i32) -> ! {
+    let mut handler_bytes = Vec::new();
+    if let Err(err) = io::stdin().read_to_end(&mut handler_bytes) {
+        eprintln!("failed to read handler: {}", err);
+        process::exit(SANDBOX_PANIC_EXIT_CODE);
+    }
+    match std::panic::catch_unwind(|| run_sandbox(&handler_bytes)) {
+        Ok(code) => process::exit(code),
+        Err(_) => process::exit(SANDBOX_PANIC_EXIT_CODE),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// Runs `script` in a shell that reads it from stdin, as a sandbox binary
+    /// reads its handler.
+    fn run(script: &str) -> Result<i32, TaskError> {
+        SandboxedTask::new("/bin/sh", script.as_bytes().to_vec()).execute()
+    }
+
+    #[test]
+    fn exit_code_is_the_result() {
+        assert_eq!(run("exit 0"), Ok(0));
+        assert_eq!(run("exit 7"), Ok(7));
+    }
+
+    #[test]
+    fn panic_exit_code_fails_with_the_child_stderr() {
+        assert_eq!(
+            run("echo 'handler panicked' >&2; exit 101"),
+            Err(TaskError::HandlerPanic("handler panicked".into()))
+        );
+    }
+
+    #[test]
+    fn crashed_child_fails_the_task() {
+        assert_eq!(
+            run("kill -9 $$"),
+            Err(TaskError::Failed("/bin/sh terminated by signal".into()))
+        );
+    }
+
+    #[test]
+    fn child_may_exit_without_reading_all_its_input() {
+        let script = format!("exit 3\n{}", "#".repeat(1 << 20));
+        assert_eq!(run(&script), Ok(3));
+    }
+
+    #[test]
+    fn missing_binary_fails_the_task() {
+        let result = SandboxedTask::new("/nonexistent/sandbox", Vec::new()).execute();
+        assert!(
+            matches!(&result, Err(TaskError::Failed(message)) if message.starts_with("failed to run /nonexistent/sandbox")),
+            "{result:?}"
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use core::fmt;
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::PriorityLevel;
+
+impl Serialize for PriorityLevel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+struct PriorityLevelVisitor;
+
+impl Visitor<'_> for PriorityLevelVisitor {
+    type Value = PriorityLevel;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a priority level such as \"high\", \"Medium\" or \"3\"")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<PriorityLevel, E> {
+        value.parse().map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for PriorityLevel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(PriorityLevelVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use alloc::string::ToString;
+
+    #[test]
+    fn every_level_round_trips_as_lowercase() {
+        for level in [
+            PriorityLevel::High,
+            PriorityLevel::Medium,
+            PriorityLevel::Low,
+        ] {
+            let json = serde_json::to_string(&level).unwrap();
+            assert_eq!(json, format!("\"{}\"", level.as_str()));
+            assert_eq!(serde_json::from_str::<PriorityLevel>(&json).unwrap(), level);
+        }
+    }
+
+    #[test]
+    fn deserialization_ignores_case_and_accepts_numbers() {
+        for json in ["\"high\"", "\"HIGH\"", "\"High\"", "\"1\""] {
+            assert_eq!(
+                serde_json::from_str::<PriorityLevel>(json).unwrap(),
+                PriorityLevel::High
+            );
+        }
+        assert_eq!(
+            serde_json::from_str::<PriorityLevel>("\"mEdIuM\"").unwrap(),
+            PriorityLevel::Medium
+        );
+        assert_eq!(
+            serde_json::from_str::<PriorityLevel>("\"3\"").unwrap(),
+            PriorityLevel::Low
+        );
+    }
+
+    #[test]
+    fn unknown_values_are_rejected() {
+        for json in ["\"urgent\"", "\"4\"", "\"\"", "1"] {
+            assert!(
+                serde_json::from_str::<PriorityLevel>(json).is_err(),
+                "{json}"
+            );
+        }
+        let err = serde_json::from_str::<PriorityLevel>("\"urgent\"").unwrap_err();
+        assert!(err.to_string().contains("unknown priority level"), "{err}");
+    }
+}
@@ -0,0 +1,175 @@
+//! A priority queue split across independently locked shards, so that
+//! concurrent producers and consumers rarely contend for the same lock.
+
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::{PriorityQueue, Summary, Task, TaskQueue};
+
+/// Routes each task to one of `n_shards` [`PriorityQueue`]s by the first
+/// byte of its id, and pops from the shards in turn.
+///
+/// Priority order only holds within a shard: a pop returns the most urgent
+/// task of the first non-empty shard it tries, which may be less urgent than
+/// a task waiting in another shard. A single [`PriorityQueue`] always pops
+/// its most urgent task. Every task is still popped exactly once.
+///
+/// All methods take `&self`, so the queue can be shared between threads
+/// without an outer lock.
+///
+/// Unlike the other queues this does not implement [`TaskQueue`]: its
+/// `peek` and `find*` methods return references into the queue, which
+/// cannot outlive the lock on the shard that holds the task. It offers the
+/// same `push`, `pop`, `len`, `is_empty` and `handle` as inherent methods
+/// instead.
+pub struct ShardedPriorityQueue {
+    shards: Vec<Mutex<PriorityQueue>>,
+    n_shards: usize,
+    next_shard: AtomicUsize,
+}
+
+impl ShardedPriorityQueue {
+    pub fn new(n_shards: usize) -> Self {
+        let n_shards = n_shards.max(1);
+        ShardedPriorityQueue {
+            shards: (0..n_shards)
+                .map(|_| Mutex::new(PriorityQueue::new()))
+                .collect(),
+            n_shards,
+            next_shard: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn n_shards(&self) -> usize {
+        self.n_shards
+    }
+
+    pub fn push(&self, task: Task) {
+        let shard = task.id.as_bytes()[0] as usize % self.n_shards;
+        self.shards[shard].lock().unwrap().push(task);
+    }
+
+    /// Tries each shard once, starting after the one the previous pop
+    /// started at, and returns the first task found.
+    #[must_use = "a popped task that is dropped never runs"]
+    pub fn pop(&self) -> Option<Task> {
+        let start = self.next_shard.fetch_add(1, Ordering::Relaxed);
+        (0..self.n_shards).find_map(|offset| {
+            let shard = (start + offset) % self.n_shards;
+            self.shards[shard].lock().unwrap().pop_simple()
+        })
+    }
+
+    /// The total across shards. Concurrent pushes and pops may make it stale
+    /// by the time it is returned.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards
+            .iter()
+            .all(|shard| shard.lock().unwrap().is_empty())
+    }
+
+    /// Takes each shard's tasks in turn and handles them with
+    /// [`TaskQueue::handle`]. Tasks pushed meanwhile are left for the next
+    /// call.
+    pub fn handle(&self) -> Summary {
+        let mut summary = Summary::default();
+        for shard in &self.shards {
+            let mut tasks = mem::take(&mut *shard.lock().unwrap());
+            summary.merge(&tasks.handle());
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::logger::WriterLogger;
+    use crate::{HardProblem, PriorityLevel};
+
+    /// A task whose id starts with `first_byte`, so it lands in shard
+    /// `first_byte % n_shards`.
+    fn task(first_byte: u8, index: u8, priority_level: PriorityLevel) -> Task {
+        let mut bytes = [0; 16];
+        bytes[0] = first_byte;
+        bytes[15] = index;
+        let handler = Box::new(HardProblem::new(1, 2).with_delay(std::time::Duration::ZERO));
+        Task::with_id(Uuid::from_bytes(bytes), handler, priority_level)
+    }
+
+    fn shard_lens(queue: &ShardedPriorityQueue) -> Vec<usize> {
+        queue
+            .shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().len())
+            .collect()
+    }
+
+    #[test]
+    fn push_routes_by_the_first_id_byte() {
+        let queue = ShardedPriorityQueue::new(3);
+        for (index, first_byte) in [0, 3, 4, 7, 10, 2].into_iter().enumerate() {
+            queue.push(task(first_byte, index as u8, PriorityLevel::Medium));
+        }
+
+        assert_eq!(shard_lens(&queue), [2, 3, 1]);
+        assert_eq!(queue.len(), 6);
+    }
+
+    #[test]
+    fn pop_takes_the_shards_in_turn() {
+        let queue = ShardedPriorityQueue::new(3);
+        queue.push(task(0, 0, PriorityLevel::Low));
+        queue.push(task(3, 1, PriorityLevel::High));
+        queue.push(task(1, 2, PriorityLevel::Medium));
+        queue.push(task(2, 3, PriorityLevel::Medium));
+
+        let popped: Vec<u8> = std::iter::from_fn(|| queue.pop())
+            .map(|task| task.id.as_bytes()[15])
+            .collect();
+
+        // Shard 0 gives its most urgent task first, then each shard once
+        // more in turn.
+        assert_eq!(popped, [1, 2, 3, 0]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn pop_skips_empty_shards() {
+        let queue = ShardedPriorityQueue::new(4);
+        queue.push(task(2, 0, PriorityLevel::Medium));
+        queue.push(task(6, 1, PriorityLevel::Medium));
+
+        assert!(queue.pop().is_some());
+        assert!(queue.pop().is_some());
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn handle_drains_every_shard() {
+        let queue = ShardedPriorityQueue::new(2);
+        let logger = Arc::new(WriterLogger::new(Vec::new()));
+        for shard in &queue.shards {
+            *shard.lock().unwrap() = PriorityQueue::with_logger(logger.clone());
+        }
+        for index in 0..5 {
+            queue.push(task(index, index, PriorityLevel::Medium));
+        }
+
+        assert_eq!(queue.handle().executed, 5);
+        assert!(queue.is_empty());
+        assert_eq!(logger.contents().lines().count(), 5);
+    }
+}
@@ -0,0 +1,30 @@
+use std::io;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use signal_hook::consts::SIGTERM;
+
+/// Returns a flag that is set once the process receives `SIGTERM`, for use
+/// with [`run_until_signal`](crate::worker::run_until_signal).
+pub fn install_sigterm_handler() -> io::Result<Arc<AtomicBool>> {
+    let stop = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGTERM, Arc::clone(&stop))?;
+    Ok(stop)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+
+    #[test]
+    fn sigterm_sets_the_flag() {
+        let stop = install_sigterm_handler().unwrap();
+        assert!(!stop.load(Ordering::Relaxed));
+
+        signal_hook::low_level::raise(SIGTERM).unwrap();
+
+        assert!(stop.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use uuid::Uuid;
+
+use crate::Task;
+
+/// Per-worker FIFO queues. A task always lands on the same worker, chosen
+/// from the first byte of its id, so workers can keep per-task state warm
+/// and never contend with each other on pop.
+pub struct StickyWorkerQueue {
+    workers: Vec<Mutex<VecDeque<Task>>>,
+    n: usize,
+}
+
+impl StickyWorkerQueue {
+    pub fn new(n_workers: usize) -> Self {
+        let n = n_workers.max(1);
+        StickyWorkerQueue {
+            workers: (0..n).map(|_| Mutex::new(VecDeque::new())).collect(),
+            n,
+        }
+    }
+
+    pub fn worker_for(&self, id: Uuid) -> usize {
+        id.as_bytes()[0] as usize % self.n
+    }
+
+    pub fn push(&self, task: Task) {
+        let worker = self.worker_for(task.id);
+        self.workers[worker].lock().unwrap().push_back(task);
+    }
+
+    /// Pops the oldest task routed to `worker`.
+    pub fn pop(&self, worker: usize) -> Option<Task> {
+        self.workers[worker].lock().unwrap().pop_front()
+    }
+
+    pub fn worker_len(&self, worker: usize) -> usize {
+        self.workers[worker].lock().unwrap().len()
+    }
+
+    pub fn len(&self) -> usize {
+        (0..self.n).map(|worker| self.worker_len(worker)).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn n_workers(&self) -> usize {
+        self.n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HardProblem, PriorityLevel};
+
+    /// A task whose id starts with `first_byte`.
+    fn task(first_byte: u8, rest: u8) -> Task {
+        let mut bytes = [rest; 16];
+        bytes[0] = first_byte;
+        Task::with_id(
+            Uuid::from_bytes(bytes),
+            Box::new(HardProblem::new(1, 2)),
+            PriorityLevel::Medium,
+        )
+    }
+
+    #[test]
+    fn tasks_stay_with_the_worker_their_id_picks() {
+        let queue = StickyWorkerQueue::new(3);
+        let tasks = [task(0, 1), task(4, 2), task(3, 3), task(7, 4)];
+        let ids: Vec<Uuid> = tasks.iter().map(|task| task.id).collect();
+        for task in tasks {
+            queue.push(task);
+        }
+
+        assert_eq!(
+            ids.iter()
+                .map(|id| queue.worker_for(*id))
+                .collect::<Vec<_>>(),
+            [0, 1, 0, 1]
+        );
+        assert_eq!(
+            (
+                queue.worker_len(0),
+                queue.worker_len(1),
+                queue.worker_len(2)
+            ),
+            (2, 2, 0)
+        );
+        assert_eq!(queue.len(), 4);
+
+        // Each worker sees only its own tasks, oldest first.
+        assert_eq!(queue.pop(2).map(|task| task.id), None);
+        assert_eq!(queue.pop(1).map(|task| task.id), Some(ids[1]));
+        assert_eq!(queue.pop(0).map(|task| task.id), Some(ids[0]));
+        assert_eq!(queue.pop(0).map(|task| task.id), Some(ids[2]));
+        assert_eq!(queue.pop(0).map(|task| task.id), None);
+        assert_eq!(queue.pop(1).map(|task| task.id), Some(ids[3]));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn zero_workers_means_one() {
+        let queue = StickyWorkerQueue::new(0);
+        assert_eq!(queue.n_workers(), 1);
+        queue.push(task(255, 0));
+        assert_eq!(queue.worker_len(0), 1);
+    }
+}
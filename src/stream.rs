@@ -0,0 +1,155 @@
+//! Async access to a [`PriorityQueue`], so a task queue can be awaited
+//! alongside other futures, e.g. in `tokio::select!`.
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures_util::Stream;
+
+use crate::{PriorityQueue, Task, TaskQueue};
+
+/// A [`Stream`] of the tasks in a shared [`PriorityQueue`], highest priority
+/// first. Clones share the queue: push from any of them, and consume from
+/// one. While the queue is empty the stream waits for the next
+/// [`push`](TaskQueueStream::push); it ends once it is empty and
+/// [`close`](TaskQueueStream::close) has been called.
+#[derive(Clone)]
+pub struct TaskQueueStream {
+    queue: Arc<tokio::sync::Mutex<PriorityQueue>>,
+    waker: Arc<Mutex<Option<Waker>>>,
+    closed: Arc<AtomicBool>,
+}
+
+impl TaskQueueStream {
+    pub fn new(queue: PriorityQueue) -> Self {
+        TaskQueueStream {
+            queue: Arc::new(tokio::sync::Mutex::new(queue)),
+            waker: Arc::new(Mutex::new(None)),
+            closed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub async fn push(&self, task: Task) {
+        self.queue.lock().await.push(task);
+        self.wake();
+    }
+
+    /// Ends the stream once the queued tasks have been yielded.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.wake();
+    }
+
+    pub async fn len(&self) -> usize {
+        self.queue.lock().await.len()
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.queue.lock().await.is_empty()
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Default for TaskQueueStream {
+    fn default() -> Self {
+        TaskQueueStream::new(PriorityQueue::new())
+    }
+}
+
+impl Stream for TaskQueueStream {
+    type Item = Task;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Task>> {
+        // Registered before checking the queue, so a push that lands between
+        // the check and returning `Pending` still wakes this task.
+        *self.waker.lock().unwrap() = Some(cx.waker().clone());
+        let closed = self.closed.load(Ordering::SeqCst);
+        let Ok(mut queue) = self.queue.try_lock() else {
+            // A push holds the lock; try again rather than wait on it.
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        };
+        match queue.pop_simple() {
+            Some(task) => Poll::Ready(Some(task)),
+            None if closed => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+
+    use super::*;
+    use crate::{HardProblem, PriorityLevel};
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn task(priority_level: PriorityLevel) -> Task {
+        Task::new(HardProblem::new(1, 2), priority_level)
+    }
+
+    #[test]
+    fn closed_stream_yields_the_queue_in_priority_order() {
+        let levels = block_on(async {
+            let stream = TaskQueueStream::default();
+            for level in [
+                PriorityLevel::Low,
+                PriorityLevel::High,
+                PriorityLevel::Medium,
+            ] {
+                stream.push(task(level)).await;
+            }
+            stream.close();
+            stream
+                .map(|task| task.priority_level)
+                .collect::<Vec<_>>()
+                .await
+        });
+        assert_eq!(
+            levels,
+            [
+                PriorityLevel::High,
+                PriorityLevel::Medium,
+                PriorityLevel::Low
+            ]
+        );
+    }
+
+    #[test]
+    fn waits_for_tasks_pushed_from_another_task() {
+        let (received, empty) = block_on(async {
+            let mut stream = TaskQueueStream::default();
+            let producer = stream.clone();
+            let pushing = tokio::spawn(async move {
+                for _ in 0..3 {
+                    tokio::task::yield_now().await;
+                    producer.push(task(PriorityLevel::Medium)).await;
+                }
+                producer.close();
+            });
+
+            let mut received = 0;
+            while stream.next().await.is_some() {
+                received += 1;
+            }
+            pushing.await.unwrap();
+            (received, stream.is_empty().await)
+        });
+        assert_eq!(received, 3);
+        assert!(empty);
+    }
+}
@@ -0,0 +1,168 @@
+//! Per-tenant limits, so one tenant's tasks cannot take over a
+//! [`WorkerPool`](crate::worker::WorkerPool).
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::rate_limit::TokenBucket;
+use crate::Task;
+
+/// Limits for the tasks whose `tenant_id` is `id`. A task over either limit
+/// stays queued, and the pool runs other tenants' tasks until it may start.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TenantConfig {
+    pub id: String,
+    /// Tasks of this tenant running at once.
+    pub max_concurrent: usize,
+    /// Tasks of this tenant started per second, spaced by a token bucket.
+    pub max_tasks_per_second: f64,
+}
+
+struct TenantState {
+    max_concurrent: usize,
+    running: usize,
+    bucket: TokenBucket,
+}
+
+/// Tracks how many tasks each configured tenant is running. Tasks without a
+/// tenant, or whose tenant has no config, are never held back.
+#[derive(Default)]
+pub(crate) struct TenantLimiter {
+    tenants: Mutex<HashMap<String, TenantState>>,
+}
+
+impl TenantLimiter {
+    pub(crate) fn add(&mut self, config: TenantConfig) {
+        let state = TenantState {
+            max_concurrent: config.max_concurrent,
+            running: 0,
+            bucket: TokenBucket::new(config.max_tasks_per_second, Instant::now()),
+        };
+        self.tenants.get_mut().unwrap().insert(config.id, state);
+    }
+
+    pub(crate) fn may_start(&self, task: &Task) -> bool {
+        let Some(tenant_id) = &task.tenant_id else {
+            return true;
+        };
+        let mut tenants = self.tenants.lock().unwrap();
+        tenants.get_mut(tenant_id).is_none_or(|tenant| {
+            tenant.running < tenant.max_concurrent && tenant.bucket.has_token(Instant::now())
+        })
+    }
+
+    /// Counts `task` as running. Call only after `may_start` returned `true`
+    /// for it, and pair with `finish`.
+    pub(crate) fn start(&self, task: &Task) {
+        let Some(tenant_id) = &task.tenant_id else {
+            return;
+        };
+        if let Some(tenant) = self.tenants.lock().unwrap().get_mut(tenant_id) {
+            tenant.running += 1;
+            tenant.bucket.take_token();
+        }
+    }
+
+    pub(crate) fn finish(&self, tenant_id: Option<&str>) {
+        let Some(tenant_id) = tenant_id else {
+            return;
+        };
+        if let Some(tenant) = self.tenants.lock().unwrap().get_mut(tenant_id) {
+            tenant.running = tenant.running.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::logger::WriterLogger;
+    use crate::worker::WorkerPool;
+    use crate::{PriorityLevel, TaskError, TaskHandler, TaskQueue};
+
+    /// How many tasks of one tenant are running, and the most seen at once.
+    #[derive(Default)]
+    struct Concurrency {
+        running: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    struct Tracked(Arc<Concurrency>);
+
+    impl TaskHandler for Tracked {
+        fn execute(&self) -> Result<i32, TaskError> {
+            let running = self.0.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.0.peak.fetch_max(running, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            self.0.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(0)
+        }
+    }
+
+    fn tenant(id: &str, max_concurrent: usize, max_tasks_per_second: f64) -> TenantConfig {
+        TenantConfig {
+            id: id.into(),
+            max_concurrent,
+            max_tasks_per_second,
+        }
+    }
+
+    fn tenant_task(handler: impl TaskHandler + Send + Sync + 'static, id: &str) -> Task {
+        let mut task = Task::new(handler, PriorityLevel::Medium);
+        task.tenant_id = Some(id.into());
+        task
+    }
+
+    fn quiet(pool: WorkerPool) -> WorkerPool {
+        pool.with_logger(Arc::new(WriterLogger::new(Vec::new())))
+    }
+
+    #[test]
+    fn each_tenant_stays_within_its_concurrency_limit() {
+        let pool = quiet(WorkerPool::new(4))
+            .with_tenant(tenant("a", 1, 1000.0))
+            .with_tenant(tenant("b", 2, 1000.0));
+        let (a, b) = (Arc::default(), Arc::<Concurrency>::default());
+        for _ in 0..6 {
+            pool.submit(tenant_task(Tracked(Arc::clone(&a)), "a"));
+            pool.submit(tenant_task(Tracked(Arc::clone(&b)), "b"));
+        }
+
+        let summary = pool.run();
+
+        assert_eq!(summary.executed, 12);
+        assert_eq!(a.peak.load(Ordering::SeqCst), 1);
+        assert!(b.peak.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn handle_one_returns_none_when_only_a_limited_tenant_is_queued() {
+        let pool = quiet(WorkerPool::new(1)).with_tenant(tenant("a", 1, 1.0));
+        let peak = Arc::default();
+        pool.submit(tenant_task(Tracked(Arc::clone(&peak)), "a"));
+        pool.submit(tenant_task(Tracked(peak), "a"));
+
+        assert!(pool.handle_one().is_some());
+        // Its one token a second is spent, and nothing running could free it.
+        assert!(pool.handle_one().is_none());
+        assert_eq!(pool.queue().lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one concurrent task")]
+    fn zero_concurrency_is_rejected() {
+        let _ = WorkerPool::new(1).with_tenant(tenant("a", 0, 1.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid rate")]
+    fn non_finite_rate_is_rejected() {
+        let _ = WorkerPool::new(1).with_tenant(tenant("a", 1, f64::NAN));
+    }
+}
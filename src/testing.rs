@@ -0,0 +1,54 @@
+//! Helpers for tests and examples.
+
+use uuid::Uuid;
+
+use crate::worker::{TaskStatus, WorkerPool};
+use crate::{Summary, Task, TaskQueue};
+
+/// A [`WorkerPool`] with its own queue, ready to take tasks.
+///
+/// ```
+/// use task_handler::testing::TestHarness;
+/// use task_handler::HardProblem;
+/// use std::time::Duration;
+///
+/// let h = TestHarness::new(2);
+/// h.submit(HardProblem::new(1, 2).with_delay(Duration::ZERO).into());
+/// let s = h.run_all();
+/// assert_eq!(s.executed, 1);
+/// h.teardown();
+/// ```
+pub struct TestHarness {
+    pool: WorkerPool,
+}
+
+impl TestHarness {
+    pub fn new(n_workers: usize) -> Self {
+        TestHarness {
+            pool: WorkerPool::new(n_workers),
+        }
+    }
+
+    pub fn pool(&self) -> &WorkerPool {
+        &self.pool
+    }
+
+    pub fn submit(&self, task: Task) {
+        self.pool.submit(task);
+    }
+
+    pub fn status(&self, id: Uuid) -> Option<TaskStatus> {
+        self.pool.status(id)
+    }
+
+    /// Runs every submitted task to completion.
+    pub fn run_all(&self) -> Summary {
+        self.pool.run()
+    }
+
+    /// Panics if any submitted task was never run.
+    pub fn teardown(self) {
+        let remaining = self.pool.queue().lock().unwrap().len();
+        assert_eq!(remaining, 0, "{} tasks were left in the queue", remaining);
+    }
+}
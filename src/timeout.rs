@@ -0,0 +1,84 @@
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::spawn;
+use std::time::{Duration, SystemTime};
+
+use crate::{catch_panics, TaskError, TaskHandler, TaskOutput};
+
+/// Fails with [`TaskError::Timeout`] if `inner` does not finish within
+/// `duration`, or by the deadline given to [`Timeout::until`]. The handler
+/// keeps running on its own thread after the timeout fires; its result is
+/// discarded.
+pub struct Timeout<H: ?Sized> {
+    inner: Arc<H>,
+    limit: Limit,
+}
+
+enum Limit {
+    /// Counted from the start of each execution.
+    After(Duration),
+    Until(SystemTime),
+}
+
+impl<H> Timeout<H>
+where
+    H: TaskHandler + Send + Sync + ?Sized + 'static,
+{
+    pub fn new(inner: impl Into<Arc<H>>, duration: Duration) -> Self {
+        Timeout {
+            inner: inner.into(),
+            limit: Limit::After(duration),
+        }
+    }
+
+    /// Times out at `deadline`, however long the task waited before it was
+    /// executed. Fails with [`TaskError::AlreadyExpired`] without running
+    /// `inner` if the deadline has passed by then.
+    pub fn until(inner: impl Into<Arc<H>>, deadline: SystemTime) -> Self {
+        Timeout {
+            inner: inner.into(),
+            limit: Limit::Until(deadline),
+        }
+    }
+}
+
+impl<H> TaskHandler for Timeout<H>
+where
+    H: TaskHandler + Send + Sync + ?Sized + 'static,
+{
+    fn execute(&self) -> Result<i32, TaskError> {
+        let duration = match self.limit {
+            Limit::After(duration) => duration,
+            Limit::Until(deadline) => deadline
+                .duration_since(SystemTime::now())
+                .ok()
+                .filter(|remaining| !remaining.is_zero())
+                .ok_or(TaskError::AlreadyExpired)?,
+        };
+        let (sender, receiver) = mpsc::channel();
+        let inner = Arc::clone(&self.inner);
+        spawn(move || {
+            let _ = sender.send(catch_panics(|| inner.execute()));
+        });
+
+        match receiver.recv_timeout(duration) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(TaskError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(TaskError::Failed(
+                "handler thread exited without a result".into(),
+            )),
+        }
+    }
+
+    fn handler_type_name(&self) -> &'static str {
+        self.inner.handler_type_name()
+    }
+
+    fn take_output(&self) -> Option<TaskOutput> {
+        self.inner.take_output()
+    }
+
+    fn cost_estimate(&self) -> Duration {
+        self.inner.cost_estimate()
+    }
+}
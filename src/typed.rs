@@ -0,0 +1,84 @@
+use core::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use crate::{TaskError, TaskHandler, TaskOutput};
+
+type TypedFn<O> = Box<dyn Fn() -> Result<O, TaskError> + Send + Sync>;
+
+/// A handler whose result is an `O` rather than an `i32`. Executing it
+/// returns `Ok(0)` and keeps the value for the executor to put in the
+/// task's outcome, where [`extract_result`] can get at it.
+///
+/// [`extract_result`]: crate::executor::extract_result
+pub struct TypedResultTask<O: Send + 'static> {
+    handler: TypedFn<O>,
+    output: Mutex<Option<TaskOutput>>,
+    _phantom: PhantomData<O>,
+}
+
+impl<O: Send + Sync + 'static> TypedResultTask<O> {
+    pub fn new(handler: impl Fn() -> Result<O, TaskError> + Send + Sync + 'static) -> Self {
+        TypedResultTask {
+            handler: Box::new(handler),
+            output: Mutex::new(None),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<O: Send + Sync + 'static> TaskHandler for TypedResultTask<O> {
+    fn execute(&self) -> Result<i32, TaskError> {
+        let output = (self.handler)()?;
+        *self.output.lock().unwrap() = Some(Arc::new(output));
+        Ok(0)
+    }
+
+    fn take_output(&self) -> Option<TaskOutput> {
+        self.output.lock().unwrap().take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::{execute_task, extract_result, ExecutionPolicy, TaskContext};
+    use crate::{PriorityLevel, Task};
+
+    fn run<O: Send + Sync + 'static>(
+        handler: impl Fn() -> Result<O, TaskError> + Send + Sync + 'static,
+    ) -> crate::executor::TaskOutcome {
+        let task = Task::new(TypedResultTask::new(handler), PriorityLevel::Medium);
+        let ctx = TaskContext::for_task(&task);
+        execute_task(task, ctx, &ExecutionPolicy::default())
+    }
+
+    #[test]
+    fn outcome_carries_the_typed_result() {
+        let outcome = run(|| Ok(String::from("report.pdf")));
+
+        assert_eq!(outcome.result, Ok(0));
+        assert_eq!(
+            extract_result::<String>(&outcome).map(String::as_str),
+            Some("report.pdf")
+        );
+        // Asking for another type finds nothing.
+        assert_eq!(extract_result::<i32>(&outcome), None);
+    }
+
+    #[test]
+    fn failure_leaves_no_typed_result() {
+        let outcome = run(|| Err::<Vec<u8>, _>(TaskError::Failed("no data".into())));
+
+        assert_eq!(outcome.result, Err(TaskError::Failed("no data".into())));
+        assert!(extract_result::<Vec<u8>>(&outcome).is_none());
+    }
+
+    #[test]
+    fn output_is_taken_once() {
+        let task = TypedResultTask::new(|| Ok(2.5f64));
+        assert_eq!(task.execute(), Ok(0));
+        let output = task.take_output().unwrap();
+        assert_eq!(output.downcast_ref::<f64>(), Some(&2.5));
+        assert!(task.take_output().is_none());
+    }
+}
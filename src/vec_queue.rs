@@ -0,0 +1,171 @@
+use alloc::vec::Vec;
+
+use uuid::Uuid;
+
+#[cfg(feature = "std")]
+use crate::Summary;
+use crate::{QueueSnapshot, Task, TaskQueue};
+
+/// A stack of tasks: pops return the most recently pushed task, ignoring
+/// priorities. Meant for tests and prototypes; use
+/// [`PriorityQueue`](crate::PriorityQueue) when priorities matter.
+#[derive(Default)]
+pub struct VecQueue {
+    tasks: Vec<Task>,
+    counts: QueueSnapshot,
+}
+
+impl VecQueue {
+    pub fn new() -> Self {
+        VecQueue::default()
+    }
+
+    fn count(&mut self) {
+        self.counts = QueueSnapshot::default();
+        for task in &self.tasks {
+            self.counts.remaining += 1;
+            *self.counts.count_mut(&task.priority_level) += 1;
+        }
+    }
+}
+
+impl From<Vec<Task>> for VecQueue {
+    fn from(tasks: Vec<Task>) -> Self {
+        let mut queue = VecQueue {
+            tasks,
+            counts: QueueSnapshot::default(),
+        };
+        queue.count();
+        queue
+    }
+}
+
+impl From<VecQueue> for Vec<Task> {
+    fn from(queue: VecQueue) -> Self {
+        queue.tasks
+    }
+}
+
+impl TaskQueue for VecQueue {
+    fn push(&mut self, task: Task) {
+        self.counts.remaining += 1;
+        *self.counts.count_mut(&task.priority_level) += 1;
+        self.tasks.push(task);
+    }
+
+    fn pop(&mut self) -> Option<(Task, QueueSnapshot)> {
+        let task = self.tasks.pop()?;
+        self.counts.remaining -= 1;
+        *self.counts.count_mut(&task.priority_level) -= 1;
+        Some((task, self.counts.clone()))
+    }
+
+    fn peek(&self) -> Option<&Task> {
+        self.tasks.last()
+    }
+
+    fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    fn task_position(&self, id: Uuid) -> Option<usize> {
+        self.tasks.iter().rev().position(|task| task.id == id)
+    }
+
+    fn find<F: Fn(&Task) -> bool>(&self, predicate: F) -> Option<&Task> {
+        self.tasks.iter().rev().find(|task| predicate(task))
+    }
+
+    fn find_all<F: Fn(&Task) -> bool>(&self, predicate: F) -> Vec<&Task> {
+        self.tasks
+            .iter()
+            .rev()
+            .filter(|task| predicate(task))
+            .collect()
+    }
+
+    fn map_in_place<F: FnMut(&mut Task)>(&mut self, f: F) {
+        self.tasks.iter_mut().for_each(f);
+        // `f` may have changed priorities.
+        self.count();
+    }
+
+    /// Runs every task on the current thread, most recently pushed first.
+    #[cfg(feature = "std")]
+    fn handle(&mut self) -> Summary {
+        self.handle_blocking()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+
+    use super::*;
+    use crate::{PriorityLevel, TaskError, TaskHandler};
+
+    struct Returns(i32);
+
+    impl TaskHandler for Returns {
+        fn execute(&self) -> Result<i32, TaskError> {
+            Ok(self.0)
+        }
+    }
+
+    fn task(id: u128, priority_level: PriorityLevel) -> Task {
+        Task::with_id(
+            Uuid::from_u128(id),
+            Box::new(Returns(id as i32)),
+            priority_level,
+        )
+    }
+
+    #[test]
+    fn pops_the_most_recent_task_first_whatever_its_priority() {
+        let mut queue = VecQueue::new();
+        queue.push(task(1, PriorityLevel::High));
+        queue.push(task(2, PriorityLevel::Low));
+        queue.push(task(3, PriorityLevel::Medium));
+
+        assert_eq!(queue.peek().map(|task| task.id), Some(Uuid::from_u128(3)));
+        assert_eq!(queue.task_position(Uuid::from_u128(1)), Some(2));
+
+        let (popped, snapshot) = queue.pop().unwrap();
+        assert_eq!(popped.id, Uuid::from_u128(3));
+        assert_eq!(
+            snapshot,
+            QueueSnapshot {
+                remaining: 2,
+                high_count: 1,
+                medium_count: 0,
+                low_count: 1,
+            }
+        );
+        let rest: Vec<Uuid> = core::iter::from_fn(|| queue.pop_simple())
+            .map(|task| task.id)
+            .collect();
+        assert_eq!(rest, [Uuid::from_u128(2), Uuid::from_u128(1)]);
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn counts_follow_changes_made_in_place() {
+        let mut queue = VecQueue::from(alloc::vec![
+            task(1, PriorityLevel::Low),
+            task(2, PriorityLevel::Low)
+        ]);
+        queue.map_in_place(|task| task.priority_level = PriorityLevel::High);
+
+        let (_, snapshot) = queue.pop().unwrap();
+        assert_eq!(
+            (snapshot.remaining, snapshot.high_count, snapshot.low_count),
+            (1, 1, 0)
+        );
+        let tasks: Vec<Task> = queue.into();
+        assert_eq!(tasks.len(), 1);
+    }
+}
@@ -0,0 +1,226 @@
+use std::sync::{Arc, Mutex};
+
+use uuid::Uuid;
+
+use crate::{QueueSnapshot, Summary, Task, TaskQueue};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatermarkState {
+    Low,
+    High,
+}
+
+type WatermarkHook = Box<dyn Fn() + Send + Sync>;
+
+/// Wraps a queue and fires `on_high` when its length rises above `high`,
+/// then `on_low` once it has fallen back below `low`. Each callback fires
+/// once per crossing.
+pub struct WatermarkQueue<Q: TaskQueue> {
+    inner: Q,
+    high: usize,
+    low: usize,
+    state: Arc<Mutex<WatermarkState>>,
+    on_high: WatermarkHook,
+    on_low: WatermarkHook,
+}
+
+impl<Q: TaskQueue> WatermarkQueue<Q> {
+    pub fn new<H, L>(inner: Q, low: usize, high: usize, on_high: H, on_low: L) -> Self
+    where
+        H: Fn() + Send + Sync + 'static,
+        L: Fn() + Send + Sync + 'static,
+    {
+        WatermarkQueue {
+            inner,
+            high,
+            low,
+            state: Arc::new(Mutex::new(WatermarkState::Low)),
+            on_high: Box::new(on_high),
+            on_low: Box::new(on_low),
+        }
+    }
+
+    pub fn state(&self) -> WatermarkState {
+        *self.state.lock().unwrap()
+    }
+
+    pub fn into_inner(self) -> Q {
+        self.inner
+    }
+
+    fn check(&self) {
+        let len = self.inner.len();
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            WatermarkState::Low if len > self.high => {
+                *state = WatermarkState::High;
+                (self.on_high)();
+            }
+            WatermarkState::High if len < self.low => {
+                *state = WatermarkState::Low;
+                (self.on_low)();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<Q: TaskQueue> TaskQueue for WatermarkQueue<Q> {
+    fn push(&mut self, task: Task) {
+        self.inner.push(task);
+        self.check();
+    }
+
+    fn pop(&mut self) -> Option<(Task, QueueSnapshot)> {
+        let task = self.inner.pop();
+        self.check();
+        task
+    }
+
+    fn peek(&self) -> Option<&Task> {
+        self.inner.peek()
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn len_estimate(&self) -> usize {
+        self.inner.len_estimate()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    fn task_position(&self, id: Uuid) -> Option<usize> {
+        self.inner.task_position(id)
+    }
+
+    fn find<F: Fn(&Task) -> bool>(&self, predicate: F) -> Option<&Task> {
+        self.inner.find(predicate)
+    }
+
+    fn find_all<F: Fn(&Task) -> bool>(&self, predicate: F) -> Vec<&Task> {
+        self.inner.find_all(predicate)
+    }
+
+    fn find_by_id(&self, id: Uuid) -> Option<&Task> {
+        self.inner.find_by_id(id)
+    }
+
+    fn map_in_place<F: FnMut(&mut Task)>(&mut self, f: F) {
+        self.inner.map_in_place(f);
+    }
+
+    /// The inner queue drains itself, so the thresholds are only checked
+    /// once it is done: a queue above `high` fires `on_low` once afterwards,
+    /// and nothing fires for the lengths it passed through on the way.
+    fn handle(&mut self) -> Summary {
+        let summary = self.inner.handle();
+        self.check();
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::logger::WriterLogger;
+    use crate::{HardProblem, PriorityLevel, PriorityQueue};
+
+    /// A queue with thresholds 2 and 4 and the number of times each callback
+    /// fired.
+    fn watermarked() -> (
+        WatermarkQueue<PriorityQueue>,
+        Arc<AtomicUsize>,
+        Arc<AtomicUsize>,
+    ) {
+        let highs = Arc::new(AtomicUsize::new(0));
+        let lows = Arc::new(AtomicUsize::new(0));
+        let queue = WatermarkQueue::new(
+            PriorityQueue::with_logger(Arc::new(WriterLogger::new(Vec::new()))),
+            2,
+            4,
+            {
+                let highs = Arc::clone(&highs);
+                move || {
+                    highs.fetch_add(1, Ordering::SeqCst);
+                }
+            },
+            {
+                let lows = Arc::clone(&lows);
+                move || {
+                    lows.fetch_add(1, Ordering::SeqCst);
+                }
+            },
+        );
+        (queue, highs, lows)
+    }
+
+    fn push(queue: &mut WatermarkQueue<PriorityQueue>, n: usize) {
+        for _ in 0..n {
+            queue.push(Task::new(
+                HardProblem::new(1, 2).with_delay(std::time::Duration::ZERO),
+                PriorityLevel::Medium,
+            ));
+        }
+    }
+
+    fn pop(queue: &mut WatermarkQueue<PriorityQueue>, n: usize) {
+        for _ in 0..n {
+            assert!(queue.pop_simple().is_some());
+        }
+    }
+
+    #[test]
+    fn callbacks_fire_once_per_crossing() {
+        let (mut queue, highs, lows) = watermarked();
+        push(&mut queue, 4);
+        assert_eq!(highs.load(Ordering::SeqCst), 0);
+        push(&mut queue, 1);
+        assert_eq!(queue.state(), WatermarkState::High);
+        push(&mut queue, 3);
+        assert_eq!(highs.load(Ordering::SeqCst), 1);
+
+        pop(&mut queue, 6);
+        assert_eq!(lows.load(Ordering::SeqCst), 0);
+        pop(&mut queue, 1);
+        assert_eq!(queue.state(), WatermarkState::Low);
+        pop(&mut queue, 1);
+        assert_eq!(lows.load(Ordering::SeqCst), 1);
+
+        push(&mut queue, 5);
+        assert_eq!(highs.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn lengths_between_the_thresholds_keep_the_state() {
+        let (mut queue, highs, lows) = watermarked();
+        push(&mut queue, 5);
+        // Between `low` and `high`, dipping and rising again fires nothing.
+        for _ in 0..3 {
+            pop(&mut queue, 3);
+            push(&mut queue, 3);
+        }
+        assert_eq!(queue.state(), WatermarkState::High);
+        assert_eq!(highs.load(Ordering::SeqCst), 1);
+        assert_eq!(lows.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn handle_checks_the_thresholds_once_drained() {
+        let (mut queue, highs, lows) = watermarked();
+        push(&mut queue, 6);
+
+        let summary = queue.handle();
+
+        assert_eq!(summary.executed, 6);
+        assert!(queue.is_empty());
+        assert_eq!(queue.state(), WatermarkState::Low);
+        assert_eq!(highs.load(Ordering::SeqCst), 1);
+        assert_eq!(lows.load(Ordering::SeqCst), 1);
+    }
+}
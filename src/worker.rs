@@ -0,0 +1,1190 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::executor::{execute_task, ExecutionPolicy, RetryPolicy, TaskContext, TaskOutcome};
+use crate::health::FailureRateTracker;
+use crate::idempotency::PersistentIdSet;
+use crate::logger::{Logger, WriterLogger};
+use crate::rate_limit::{PriorityRateLimiter, WindowedRateLimiter};
+use crate::tenant::{TenantConfig, TenantLimiter};
+use crate::{PriorityLevel, PriorityQueue, Summary, Task, TaskError, TaskHandler, TaskQueue};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PriorityTimeoutConfig {
+    pub high: Duration,
+    pub medium: Duration,
+    pub low: Duration,
+}
+
+impl PriorityTimeoutConfig {
+    pub fn timeout_for(&self, priority_level: &PriorityLevel) -> Duration {
+        match priority_level {
+            PriorityLevel::High => self.high,
+            PriorityLevel::Medium => self.medium,
+            PriorityLevel::Low => self.low,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskStatus {
+    Pending,
+    Running,
+    Completed(i32),
+    Failed(String),
+    Cancelled,
+}
+
+impl From<&Result<i32, TaskError>> for TaskStatus {
+    fn from(result: &Result<i32, TaskError>) -> Self {
+        match result {
+            Ok(value) => TaskStatus::Completed(*value),
+            Err(TaskError::Skipped | TaskError::AlreadyExpired) => TaskStatus::Cancelled,
+            Err(err) => TaskStatus::Failed(err.to_string()),
+        }
+    }
+}
+
+/// Settings [`WorkerPool::reconfigure`] can change while the pool runs.
+/// `None` leaves the current setting alone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueConfig {
+    pub n_workers: usize,
+    /// Tasks per second across all workers, spaced evenly. Replaces any
+    /// limiter from [`WorkerPool::with_rate_limiter`]; a rate that is not
+    /// positive removes the limit.
+    pub rate_limit: Option<f64>,
+    /// Applies to every priority level, replacing the timeouts from
+    /// [`WorkerPool::with_timeouts`].
+    pub task_timeout: Option<Duration>,
+}
+
+/// Worker threads draining a shared [`PriorityQueue`].
+pub struct WorkerPool {
+    state: Arc<PoolState>,
+    warm: Vec<WarmWorker>,
+}
+
+struct PoolState {
+    queue: Arc<Mutex<PriorityQueue>>,
+    n_workers: AtomicUsize,
+    policy: RwLock<ExecutionPolicy>,
+    statuses: Arc<Mutex<HashMap<Uuid, TaskStatus>>>,
+    idempotency_store: Option<Arc<Mutex<PersistentIdSet>>>,
+    rate_limiter: RwLock<Option<Arc<WindowedRateLimiter>>>,
+    priority_rate_limiter: Option<Arc<PriorityRateLimiter>>,
+    logger: Arc<dyn Logger>,
+    failure_rate: Option<Arc<Mutex<FailureRateTracker>>>,
+    affinity_timeout: Duration,
+    tenants: TenantLimiter,
+    /// Tasks popped by `next_task` whose `execute` has not finished.
+    running_tasks: AtomicUsize,
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// An idle thread started by [`WorkerPool::warmup`], blocked until `run`
+/// hands it a job.
+struct WarmWorker {
+    jobs: Option<mpsc::Sender<Job>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl WarmWorker {
+    fn spawn() -> Self {
+        let (jobs, rx) = mpsc::channel::<Job>();
+        let thread = thread::spawn(move || {
+            while let Ok(job) = rx.recv() {
+                job();
+            }
+        });
+        WarmWorker {
+            jobs: Some(jobs),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for WarmWorker {
+    fn drop(&mut self) {
+        self.jobs.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Tells `run` that a worker has exited, even if it panicked.
+struct Finished {
+    worker_id: usize,
+    warm_index: Option<usize>,
+    summary: Summary,
+    tx: mpsc::Sender<(usize, Option<usize>, Summary)>,
+}
+
+impl Finished {
+    fn complete(mut self, summary: Summary) {
+        self.summary = summary;
+    }
+}
+
+impl Drop for Finished {
+    fn drop(&mut self) {
+        let summary = std::mem::take(&mut self.summary);
+        let _ = self.tx.send((self.worker_id, self.warm_index, summary));
+    }
+}
+
+impl WorkerPool {
+    pub fn new(n_workers: usize) -> Self {
+        WorkerPool::with_queue(Arc::new(Mutex::new(PriorityQueue::new())), n_workers)
+    }
+
+    pub fn with_queue(queue: Arc<Mutex<PriorityQueue>>, n_workers: usize) -> Self {
+        WorkerPool {
+            state: Arc::new(PoolState {
+                queue,
+                n_workers: AtomicUsize::new(n_workers.max(1)),
+                policy: RwLock::new(ExecutionPolicy::default()),
+                statuses: Arc::new(Mutex::new(HashMap::new())),
+                idempotency_store: None,
+                rate_limiter: RwLock::new(None),
+                priority_rate_limiter: None,
+                logger: Arc::new(WriterLogger::stdout()),
+                failure_rate: None,
+                affinity_timeout: DEFAULT_AFFINITY_TIMEOUT,
+                tenants: TenantLimiter::default(),
+                running_tasks: AtomicUsize::new(0),
+            }),
+            warm: Vec::new(),
+        }
+    }
+
+    fn state_mut(&mut self) -> &mut PoolState {
+        Arc::get_mut(&mut self.state).expect("pool state is only shared while running")
+    }
+
+    pub fn with_policy(mut self, policy: ExecutionPolicy) -> Self {
+        *self.state_mut().policy.get_mut().unwrap() = policy;
+        self
+    }
+
+    pub fn with_timeouts(mut self, timeouts: PriorityTimeoutConfig) -> Self {
+        self.state_mut().policy.get_mut().unwrap().timeouts = Some(timeouts);
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.state_mut().policy.get_mut().unwrap().retry = Some(retry);
+        self
+    }
+
+    /// Skips tasks already recorded in `store` and records every task that
+    /// completes successfully.
+    pub fn with_idempotency_store(mut self, store: Arc<Mutex<PersistentIdSet>>) -> Self {
+        self.state_mut().idempotency_store = Some(store);
+        self
+    }
+
+    /// Workers wait for `limiter` before starting each task.
+    pub fn with_rate_limiter(mut self, limiter: Arc<WindowedRateLimiter>) -> Self {
+        *self.state_mut().rate_limiter.get_mut().unwrap() = Some(limiter);
+        self
+    }
+
+    /// Throttles each priority level to `limits` tasks per second. Levels
+    /// missing from `limits` are not throttled.
+    pub fn with_priority_rate_limiter(mut self, limits: HashMap<PriorityLevel, f64>) -> Self {
+        self.state_mut().priority_rate_limiter = Some(Arc::new(PriorityRateLimiter::new(limits)));
+        self
+    }
+
+    /// Sends the pool's log lines to `logger` instead of stdout.
+    pub fn with_logger(mut self, logger: Arc<dyn Logger>) -> Self {
+        self.state_mut().logger = logger;
+        self
+    }
+
+    /// Records every task's outcome in `tracker`, and demotes tasks submitted
+    /// while it reports a failure rate above its threshold to `Low`.
+    pub fn with_failure_rate_tracker(mut self, tracker: FailureRateTracker) -> Self {
+        self.state_mut().failure_rate = Some(Arc::new(Mutex::new(tracker)));
+        self
+    }
+
+    /// How long a task with a `preferred_worker` waits for that worker before
+    /// any worker may take it. Defaults to 50ms.
+    pub fn with_affinity_timeout(mut self, timeout: Duration) -> Self {
+        self.state_mut().affinity_timeout = timeout;
+        self
+    }
+
+    /// Limits the tasks whose `tenant_id` is `config.id`. Replaces an earlier
+    /// config for the same tenant.
+    ///
+    /// Panics if `max_concurrent` is zero or `max_tasks_per_second` is not a
+    /// positive finite number, as the tenant's tasks could then never start.
+    pub fn with_tenant(mut self, config: TenantConfig) -> Self {
+        assert!(
+            config.max_concurrent > 0,
+            "tenant {} must be allowed at least one concurrent task",
+            config.id
+        );
+        assert!(
+            config.max_tasks_per_second.is_finite() && config.max_tasks_per_second > 0.0,
+            "tenant {} has invalid rate {}",
+            config.id,
+            config.max_tasks_per_second
+        );
+        self.state_mut().tenants.add(config);
+        self
+    }
+
+    /// Starts threads ahead of time so that `run` does not pay for thread
+    /// creation. Calling it again only tops the pool up to `n` threads.
+    pub fn warmup(&mut self, n: usize) {
+        while self.warm.len() < n {
+            self.warm.push(WarmWorker::spawn());
+        }
+    }
+
+    pub fn queue(&self) -> Arc<Mutex<PriorityQueue>> {
+        Arc::clone(&self.state.queue)
+    }
+
+    /// Spawns a thread that, every `check_interval`, promotes queued tasks
+    /// that have waited `max_age` by one level (see
+    /// [`PriorityQueue::promote_older_than`]), so that a steady stream of
+    /// urgent tasks cannot starve the rest. The thread exits once the queue
+    /// is dropped.
+    pub fn start_age_promoter(&self, check_interval: Duration, max_age: Duration) {
+        let queue = Arc::downgrade(&self.state.queue);
+        thread::spawn(move || loop {
+            thread::sleep(check_interval);
+            let Some(queue) = queue.upgrade() else {
+                return;
+            };
+            queue.lock().unwrap().promote_older_than(max_age);
+        });
+    }
+
+    pub fn submit(&self, mut task: Task) {
+        demote_if_degraded(&self.state.failure_rate, &mut task);
+        self.state.set_status(task.id, TaskStatus::Pending);
+        self.state.queue.lock().unwrap().push(task);
+    }
+
+    pub fn submitter(&self) -> TaskSubmitter {
+        TaskSubmitter {
+            queue: Arc::clone(&self.state.queue),
+            statuses: Arc::clone(&self.state.statuses),
+            failure_rate: self.state.failure_rate.clone(),
+        }
+    }
+
+    /// The last status recorded for `id`. Finished tasks keep their status
+    /// until [`forget`](Self::forget) removes it.
+    pub fn status(&self, id: Uuid) -> Option<TaskStatus> {
+        self.state.statuses.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Removes and returns the status of a finished task, so that a
+    /// long-lived pool does not keep one entry per task it has ever run.
+    /// Pending and running tasks are left alone and yield `None`.
+    pub fn forget(&self, id: Uuid) -> Option<TaskStatus> {
+        let mut statuses = self.state.statuses.lock().unwrap();
+        match statuses.get(&id)? {
+            TaskStatus::Pending | TaskStatus::Running => None,
+            _ => statuses.remove(&id),
+        }
+    }
+
+    pub fn n_workers(&self) -> usize {
+        self.state.n_workers()
+    }
+
+    /// Applies `config` to a pool that may be running. A running [`run`]
+    /// spawns extra workers on its next poll; surplus workers exit once
+    /// their current task finishes. The rate limit and timeout apply to
+    /// tasks started afterwards.
+    ///
+    /// [`run`]: WorkerPool::run
+    pub fn reconfigure(&self, config: QueueConfig) {
+        if let Some(rate) = config.rate_limit {
+            // Also false for NaN, which removes the limit like zero does.
+            let limiter = (rate > 0.0).then(|| {
+                let interval = Duration::try_from_secs_f64(1.0 / rate).unwrap_or(Duration::MAX);
+                Arc::new(WindowedRateLimiter::new(interval, 1))
+            });
+            *self.state.rate_limiter.write().unwrap() = limiter;
+        }
+        if let Some(timeout) = config.task_timeout {
+            self.state.policy.write().unwrap().timeouts = Some(PriorityTimeoutConfig {
+                high: timeout,
+                medium: timeout,
+                low: timeout,
+            });
+        }
+        self.state
+            .n_workers
+            .store(config.n_workers.max(1), Ordering::SeqCst);
+    }
+
+    /// Runs the workers until the queue is empty and returns the combined
+    /// summary of every task they handled. Threads from [`warmup`] are used
+    /// first. The number of workers follows [`reconfigure`] while this runs.
+    ///
+    /// [`warmup`]: WorkerPool::warmup
+    /// [`reconfigure`]: WorkerPool::reconfigure
+    pub fn run(&self) -> Summary {
+        let (tx, rx) = mpsc::channel();
+        thread::scope(|scope| {
+            let mut running = HashSet::new();
+            let mut idle: Vec<usize> = (0..self.warm.len()).rev().collect();
+            let mut summary = Summary::default();
+            loop {
+                let queue_is_empty = self.state.queue.lock().unwrap().is_empty();
+                if queue_is_empty && running.is_empty() {
+                    return summary;
+                }
+                if !queue_is_empty {
+                    for worker_id in 0..self.n_workers() {
+                        if running.insert(worker_id) {
+                            let warm_index = idle.pop();
+                            self.start_worker(scope, worker_id, warm_index, tx.clone());
+                        }
+                    }
+                }
+
+                let Ok((worker_id, warm_index, worker_summary)) =
+                    rx.recv_timeout(IDLE_POLL_INTERVAL)
+                else {
+                    continue;
+                };
+                running.remove(&worker_id);
+                idle.extend(warm_index);
+                if worker_summary == Summary::default() {
+                    // The worker found nothing it could start; give the
+                    // limits time to free up before starting it again.
+                    thread::sleep(IDLE_POLL_INTERVAL);
+                }
+                summary.merge(&worker_summary);
+            }
+        })
+    }
+
+    fn start_worker<'scope>(
+        &'scope self,
+        scope: &'scope thread::Scope<'scope, '_>,
+        worker_id: usize,
+        warm_index: Option<usize>,
+        tx: mpsc::Sender<(usize, Option<usize>, Summary)>,
+    ) {
+        let finished = Finished {
+            worker_id,
+            warm_index,
+            summary: Summary::default(),
+            tx,
+        };
+        let warm = warm_index.and_then(|index| self.warm[index].jobs.as_ref());
+        match warm {
+            Some(jobs) => {
+                let state = Arc::clone(&self.state);
+                let job: Job = Box::new(move || {
+                    let summary = state.work(worker_id);
+                    drop(state);
+                    finished.complete(summary);
+                });
+                if let Err(mpsc::SendError(job)) = jobs.send(job) {
+                    // The warm thread is gone; run the job on a fresh thread.
+                    scope.spawn(job);
+                }
+            }
+            None => {
+                scope.spawn(move || finished.complete(self.state.work(worker_id)));
+            }
+        }
+    }
+
+    /// Pops and executes a single task on the calling thread. Returns `None`
+    /// if the queue is empty, or if no queued task may start yet and no task
+    /// is running whose completion could change that.
+    pub fn handle_one(&self) -> Option<TaskOutcome> {
+        let task = self.state.next_task(None)?;
+        let ctx = TaskContext::for_task(&task);
+        Some(self.state.execute(task, ctx))
+    }
+}
+
+impl PoolState {
+    fn set_status(&self, id: Uuid, status: TaskStatus) {
+        self.statuses.lock().unwrap().insert(id, status);
+    }
+
+    fn n_workers(&self) -> usize {
+        self.n_workers.load(Ordering::SeqCst)
+    }
+
+    /// Pops the most urgent task `worker_id` may run (see
+    /// [`PoolState::may_run`]) whose tenant is within its limits. Waits while
+    /// there is none, or while a priority rate limiter throttles it, so a
+    /// more urgent task pushed meanwhile is picked up as soon as it arrives.
+    /// The task counts against its tenant until [`PoolState::execute`]
+    /// finishes it. Returns `None` once the queue is empty, and also when no
+    /// task is eligible and none is running, so callers get to check their
+    /// stop conditions instead of waiting on a limit.
+    fn next_task(&self, worker_id: Option<usize>) -> Option<Task> {
+        loop {
+            {
+                let mut queue = self.queue.lock().unwrap();
+                if queue.is_empty() {
+                    return None;
+                }
+                let candidate = queue
+                    .find(|task| self.may_run(task, worker_id) && self.tenants.may_start(task))
+                    .map(|task| (task.id, task.priority_level));
+                if let Some((id, priority_level)) = candidate {
+                    let throttled = self
+                        .priority_rate_limiter
+                        .as_ref()
+                        .is_some_and(|limiter| !limiter.try_acquire(priority_level));
+                    if !throttled {
+                        let task = queue.pop_by_id(id);
+                        if let Some(task) = &task {
+                            self.tenants.start(task);
+                            self.running_tasks.fetch_add(1, Ordering::SeqCst);
+                        }
+                        return task;
+                    }
+                }
+                if self.running_tasks.load(Ordering::SeqCst) == 0 {
+                    return None;
+                }
+            }
+            thread::sleep(IDLE_POLL_INTERVAL);
+        }
+    }
+
+    /// A task that prefers another worker is left for that worker until it
+    /// has waited `affinity_timeout`, unless the pool has no such worker.
+    fn may_run(&self, task: &Task, worker_id: Option<usize>) -> bool {
+        let (Some(preferred), Some(worker_id)) = (task.preferred_worker, worker_id) else {
+            return true;
+        };
+        preferred == worker_id
+            || preferred >= self.n_workers()
+            || task
+                .enqueued_at
+                .is_none_or(|enqueued_at| enqueued_at.elapsed() >= self.affinity_timeout)
+    }
+
+    fn work(&self, worker_id: usize) -> Summary {
+        let mut summary = Summary::default();
+        while worker_id < self.n_workers() {
+            let Some(task) = self.next_task(Some(worker_id)) else {
+                break;
+            };
+            let ctx = TaskContext::for_task(&task).with_worker(worker_id);
+            summary.record(&self.execute(task, ctx).result);
+        }
+        summary
+    }
+
+    fn execute(&self, task: Task, ctx: TaskContext) -> TaskOutcome {
+        let _running = RunningTask {
+            state: self,
+            tenant_id: task.tenant_id.clone(),
+        };
+        if let Some(store) = &self.idempotency_store {
+            if store.lock().unwrap().is_done(task.id) {
+                let outcome = TaskOutcome {
+                    task_id: task.id,
+                    result: Err(TaskError::Skipped),
+                    output: None,
+                };
+                self.set_status(outcome.task_id, TaskStatus::from(&outcome.result));
+                return outcome;
+            }
+        }
+
+        let limiter = self.rate_limiter.read().unwrap().clone();
+        if let Some(limiter) = limiter {
+            while !limiter.try_acquire() {
+                thread::sleep(IDLE_POLL_INTERVAL);
+            }
+        }
+
+        self.set_status(task.id, TaskStatus::Running);
+        let correlation_id = ctx.child_correlation_id();
+        let handler_type_name = task.handler.handler_type_name();
+        let mut policy = self.policy.read().unwrap().clone();
+        policy
+            .logger
+            .get_or_insert_with(|| Arc::clone(&self.logger));
+        let outcome = execute_task(task, ctx, &policy);
+        if let Some(tracker) = &self.failure_rate {
+            match &outcome.result {
+                Err(TaskError::Skipped | TaskError::AlreadyExpired) => {}
+                result => tracker.lock().unwrap().record(result.is_err()),
+            }
+        }
+        if let Err(err) = &outcome.result {
+            self.logger.log(&format!(
+                "Task {} (correlation {}, handler {}) failed: {}",
+                outcome.task_id, correlation_id, handler_type_name, err
+            ));
+        }
+        if let (Some(store), Ok(_)) = (&self.idempotency_store, &outcome.result) {
+            if let Err(err) = store.lock().unwrap().mark_done(outcome.task_id) {
+                self.logger.log(&format!(
+                    "Failed to record task {} (correlation {}) as done: {}",
+                    outcome.task_id, correlation_id, err
+                ));
+            }
+        }
+        self.set_status(outcome.task_id, TaskStatus::from(&outcome.result));
+        outcome
+    }
+}
+
+/// Counts a task as running until it is done, even if it panicked, and then
+/// frees its tenant's slot.
+struct RunningTask<'a> {
+    state: &'a PoolState,
+    tenant_id: Option<String>,
+}
+
+impl Drop for RunningTask<'_> {
+    fn drop(&mut self) {
+        self.state.tenants.finish(self.tenant_id.as_deref());
+        self.state.running_tasks.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Submits tasks spawned by a running task, tagging them with the parent's
+/// correlation id so the whole chain can be traced back to its root.
+#[derive(Clone)]
+pub struct TaskSubmitter {
+    queue: Arc<Mutex<PriorityQueue>>,
+    statuses: Arc<Mutex<HashMap<Uuid, TaskStatus>>>,
+    failure_rate: Option<Arc<Mutex<FailureRateTracker>>>,
+}
+
+impl TaskSubmitter {
+    pub fn submit(&self, parent: &TaskContext, mut task: Task) {
+        task.correlation_id = Some(parent.child_correlation_id());
+        demote_if_degraded(&self.failure_rate, &mut task);
+        self.statuses
+            .lock()
+            .unwrap()
+            .insert(task.id, TaskStatus::Pending);
+        self.queue.lock().unwrap().push(task);
+    }
+}
+
+fn demote_if_degraded(tracker: &Option<Arc<Mutex<FailureRateTracker>>>, task: &mut Task) {
+    if tracker
+        .as_ref()
+        .is_some_and(|tracker| tracker.lock().unwrap().is_degraded())
+    {
+        task.priority_level = PriorityLevel::Low;
+    }
+}
+
+/// Runs a single handler through a throwaway one-worker pool and returns its
+/// result. A failure is also logged to stdout.
+pub fn submit_and_wait(
+    handler: impl TaskHandler + Send + Sync + 'static,
+    priority_level: PriorityLevel,
+) -> Result<i32, TaskError> {
+    submit_and_wait_with_logger(handler, priority_level, Arc::new(WriterLogger::stdout()))
+}
+
+/// Like [`submit_and_wait`], but the throwaway pool logs to `logger`.
+pub fn submit_and_wait_with_logger(
+    handler: impl TaskHandler + Send + Sync + 'static,
+    priority_level: PriorityLevel,
+    logger: Arc<dyn Logger>,
+) -> Result<i32, TaskError> {
+    let pool = WorkerPool::new(1).with_logger(logger);
+    pool.submit(Task::new(handler, priority_level));
+    pool.handle_one()
+        .expect("the submitted task is still queued")
+        .result
+}
+
+pub(crate) const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+const DEFAULT_AFFINITY_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Handles tasks one at a time on the calling thread until `stop` is set,
+/// polling while the queue is empty. The flag is checked between tasks, so
+/// a task that is already running is allowed to finish.
+pub fn run_until_signal(pool: &WorkerPool, stop: Arc<AtomicBool>) -> Summary {
+    let mut summary = Summary::default();
+    while !stop.load(Ordering::Relaxed) {
+        match pool.handle_one() {
+            Some(outcome) => summary.record(&outcome.result),
+            None => thread::sleep(IDLE_POLL_INTERVAL),
+        }
+    }
+    summary
+}
+
+/// Tasks submitted through a [`scope`] call.
+pub struct TaskScope<'pool> {
+    pool: &'pool WorkerPool,
+    submitted: Mutex<Vec<Uuid>>,
+}
+
+impl TaskScope<'_> {
+    pub fn submit(&self, task: Task) {
+        self.submitted.lock().unwrap().push(task.id);
+        self.pool.submit(task);
+    }
+
+    /// Blocks until every task submitted through this scope has finished.
+    pub fn wait_all(&self) {
+        while self.submitted.lock().unwrap().iter().any(|id| {
+            matches!(
+                self.pool.status(*id),
+                Some(TaskStatus::Pending | TaskStatus::Running)
+            )
+        }) {
+            thread::sleep(IDLE_POLL_INTERVAL);
+        }
+    }
+}
+
+struct StopOnDrop(Arc<AtomicBool>);
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Runs the pool's workers for the duration of `f`, like
+/// [`std::thread::scope`]. Once `f` returns, waits for every task submitted
+/// through the [`TaskScope`] before stopping the workers, so no worker
+/// thread outlives the call.
+pub fn scope<F, R>(pool: &WorkerPool, f: F) -> R
+where
+    F: FnOnce(&TaskScope<'_>) -> R,
+{
+    let stop = Arc::new(AtomicBool::new(false));
+    thread::scope(|s| {
+        for _ in 0..pool.n_workers() {
+            let stop = Arc::clone(&stop);
+            s.spawn(move || run_until_signal(pool, stop));
+        }
+
+        let _stop = StopOnDrop(Arc::clone(&stop));
+        let task_scope = TaskScope {
+            pool,
+            submitted: Mutex::new(Vec::new()),
+        };
+        let result = f(&task_scope);
+        task_scope.wait_all();
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Sleeps for the given time, then succeeds.
+    struct Sleeps(Duration);
+
+    impl TaskHandler for Sleeps {
+        fn execute(&self) -> Result<i32, TaskError> {
+            thread::sleep(self.0);
+            Ok(0)
+        }
+    }
+
+    fn quiet(pool: WorkerPool) -> WorkerPool {
+        pool.with_logger(Arc::new(WriterLogger::new(Vec::new())))
+    }
+
+    #[test]
+    fn timeouts_follow_the_task_priority() {
+        let pool = quiet(WorkerPool::new(2)).with_timeouts(PriorityTimeoutConfig {
+            high: Duration::from_secs(10),
+            medium: Duration::from_secs(10),
+            low: Duration::from_millis(100),
+        });
+        let low = Task::new(Sleeps(Duration::from_millis(300)), PriorityLevel::Low);
+        let high = Task::new(Sleeps(Duration::from_millis(300)), PriorityLevel::High);
+        let (low_id, high_id) = (low.id, high.id);
+        pool.submit(low);
+        pool.submit(high);
+
+        let summary = pool.run();
+
+        assert_eq!(summary.executed, 1);
+        assert_eq!(summary.failed, 1);
+        assert_eq!(
+            pool.status(low_id),
+            Some(TaskStatus::Failed(TaskError::Timeout.to_string()))
+        );
+        assert_eq!(pool.status(high_id), Some(TaskStatus::Completed(0)));
+    }
+
+    #[test]
+    fn status_reaches_completed_with_the_result() {
+        let pool = quiet(WorkerPool::new(1));
+        let task = Task::new(
+            crate::HardProblem::new(20, 22).with_delay(Duration::from_millis(50)),
+            PriorityLevel::Medium,
+        );
+        let id = task.id;
+        pool.submit(task);
+        assert_eq!(pool.status(id), Some(TaskStatus::Pending));
+
+        thread::scope(|s| {
+            s.spawn(|| pool.run());
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while pool.status(id) != Some(TaskStatus::Completed(42)) {
+                assert!(Instant::now() < deadline, "status: {:?}", pool.status(id));
+                thread::sleep(Duration::from_millis(5));
+            }
+        });
+    }
+
+    #[test]
+    fn forget_removes_only_finished_statuses() {
+        let pool = quiet(WorkerPool::new(1));
+        let first = Task::new(Sleeps(Duration::ZERO), PriorityLevel::High);
+        let second = Task::new(Sleeps(Duration::ZERO), PriorityLevel::Low);
+        let (first_id, second_id) = (first.id, second.id);
+        pool.submit(first);
+        pool.submit(second);
+        pool.handle_one();
+
+        assert_eq!(pool.forget(second_id), None);
+        assert_eq!(pool.status(second_id), Some(TaskStatus::Pending));
+        assert_eq!(pool.forget(first_id), Some(TaskStatus::Completed(0)));
+        assert_eq!(pool.status(first_id), None);
+        assert_eq!(pool.forget(first_id), None);
+    }
+
+    #[test]
+    fn shrinking_the_pool_retires_the_surplus_workers() {
+        /// Records how many tasks run at once, once `measuring` is set.
+        struct Gauge {
+            active: Arc<AtomicUsize>,
+            peak: Arc<AtomicUsize>,
+            measuring: Arc<AtomicBool>,
+        }
+
+        impl TaskHandler for Gauge {
+            fn execute(&self) -> Result<i32, TaskError> {
+                let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+                if self.measuring.load(Ordering::SeqCst) {
+                    self.peak.fetch_max(active, Ordering::SeqCst);
+                }
+                thread::sleep(Duration::from_millis(20));
+                self.active.fetch_sub(1, Ordering::SeqCst);
+                Ok(0)
+            }
+        }
+
+        let pool = quiet(WorkerPool::new(8));
+        let (active, peak, measuring) = Default::default();
+        let gauge = || Gauge {
+            active: Arc::clone(&active),
+            peak: Arc::clone(&peak),
+            measuring: Arc::clone(&measuring),
+        };
+        for _ in 0..80 {
+            pool.submit(Task::new(gauge(), PriorityLevel::Medium));
+        }
+
+        let summary = thread::scope(|s| {
+            let run = s.spawn(|| pool.run());
+            thread::sleep(Duration::from_millis(50));
+            assert!(active.load(Ordering::SeqCst) > 2);
+            pool.reconfigure(QueueConfig {
+                n_workers: 2,
+                rate_limit: None,
+                task_timeout: None,
+            });
+            // Let the retired workers finish the task they were running.
+            thread::sleep(Duration::from_millis(100));
+            measuring.store(true, Ordering::SeqCst);
+            run.join().unwrap()
+        });
+
+        assert_eq!(pool.n_workers(), 2);
+        assert_eq!(summary.executed, 80);
+        assert!(peak.load(Ordering::SeqCst) <= 2, "peak {peak:?}");
+    }
+
+    #[test]
+    fn reconfigured_rate_limit_spaces_out_tasks() {
+        let pool = quiet(WorkerPool::new(1));
+        let config = |rate_limit| QueueConfig {
+            n_workers: 1,
+            rate_limit: Some(rate_limit),
+            task_timeout: None,
+        };
+        let handle = |n| {
+            for _ in 0..n {
+                pool.submit(Task::new(Sleeps(Duration::ZERO), PriorityLevel::Medium));
+            }
+            let started = Instant::now();
+            while pool.handle_one().is_some() {}
+            started.elapsed()
+        };
+
+        pool.reconfigure(config(20.0));
+        assert!(handle(4) >= Duration::from_millis(150));
+        // A rate that is not positive removes the limit again.
+        pool.reconfigure(config(0.0));
+        assert!(handle(4) < Duration::from_millis(150));
+    }
+
+    #[test]
+    fn reconfigured_timeout_applies_to_every_priority() {
+        let pool = quiet(WorkerPool::new(1));
+        pool.reconfigure(QueueConfig {
+            n_workers: 1,
+            rate_limit: None,
+            task_timeout: Some(Duration::from_millis(50)),
+        });
+        let slow = [PriorityLevel::High, PriorityLevel::Low]
+            .map(|level| Task::new(Sleeps(Duration::from_millis(300)), level));
+        let ids = slow.each_ref().map(|task| task.id);
+        let fast = Task::new(Sleeps(Duration::ZERO), PriorityLevel::Medium);
+        let fast_id = fast.id;
+        for task in slow.into_iter().chain([fast]) {
+            pool.submit(task);
+        }
+
+        let summary = pool.run();
+
+        assert_eq!((summary.executed, summary.failed), (1, 2));
+        for id in ids {
+            assert_eq!(
+                pool.status(id),
+                Some(TaskStatus::Failed(TaskError::Timeout.to_string()))
+            );
+        }
+        assert_eq!(pool.status(fast_id), Some(TaskStatus::Completed(0)));
+    }
+
+    #[test]
+    fn submit_and_wait_returns_the_handler_result() {
+        let problem = crate::HardProblem::new(3, 4).with_delay(Duration::ZERO);
+        assert_eq!(submit_and_wait(problem, PriorityLevel::Medium), Ok(7));
+    }
+
+    #[test]
+    fn submit_and_wait_returns_the_handler_error() {
+        let problem = crate::HardProblem::new(3, 0)
+            .with_operation(crate::Operation::Div)
+            .with_delay(Duration::ZERO);
+        let logger = Arc::new(WriterLogger::new(Vec::new()));
+        assert_eq!(
+            submit_and_wait_with_logger(problem, PriorityLevel::High, logger.clone()),
+            Err(TaskError::Failed("division by zero".into()))
+        );
+        assert!(logger.contents().contains("division by zero"));
+    }
+
+    #[test]
+    fn run_until_signal_handles_tasks_until_stopped() {
+        let pool = quiet(WorkerPool::new(1));
+        let stop = Arc::new(AtomicBool::new(false));
+        let ids: Vec<Uuid> = (0..3)
+            .map(|_| {
+                let task = Task::new(Sleeps(Duration::ZERO), PriorityLevel::Medium);
+                let id = task.id;
+                pool.submit(task);
+                id
+            })
+            .collect();
+
+        thread::scope(|s| {
+            let running = s.spawn(|| run_until_signal(&pool, Arc::clone(&stop)));
+            // An empty queue does not end the loop; only the flag does.
+            while ids
+                .iter()
+                .any(|id| pool.status(*id) != Some(TaskStatus::Completed(0)))
+            {
+                thread::sleep(Duration::from_millis(5));
+            }
+            thread::sleep(IDLE_POLL_INTERVAL * 3);
+            assert!(!running.is_finished());
+
+            let stopped_at = Instant::now();
+            stop.store(true, Ordering::Relaxed);
+            let summary = running.join().unwrap();
+            assert!(stopped_at.elapsed() < Duration::from_millis(500));
+            assert_eq!(summary.executed, 3);
+        });
+    }
+
+    #[test]
+    fn run_until_signal_returns_at_once_if_already_stopped() {
+        let pool = quiet(WorkerPool::new(1));
+        pool.submit(Task::new(Sleeps(Duration::ZERO), PriorityLevel::Medium));
+
+        let summary = run_until_signal(&pool, Arc::new(AtomicBool::new(true)));
+
+        assert_eq!(summary, Summary::default());
+        assert_eq!(pool.queue().lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn failure_logs_name_the_handler_type() {
+        struct Refuses;
+
+        impl TaskHandler for Refuses {
+            fn execute(&self) -> Result<i32, TaskError> {
+                Err(TaskError::Failed("refused".into()))
+            }
+        }
+
+        let log = Arc::new(WriterLogger::new(Vec::new()));
+        let pool = WorkerPool::new(1).with_logger(log.clone());
+        let problem = crate::HardProblem::new(1, 0)
+            .with_operation(crate::Operation::Div)
+            .with_delay(Duration::ZERO);
+        pool.submit(Task::new(problem, PriorityLevel::High));
+        pool.submit(Task::new(Refuses, PriorityLevel::Low));
+        pool.submit(Task::new(Sleeps(Duration::ZERO), PriorityLevel::Low));
+
+        assert_eq!(pool.run().failed, 2);
+
+        let lines: Vec<String> = log.contents().lines().map(str::to_owned).collect();
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(lines[0].contains(", handler HardProblem) failed: "));
+        // Without an override, the handler is named by its full type name.
+        assert!(lines[1].contains(&format!(
+            ", handler {}) failed: ",
+            Refuses.handler_type_name()
+        )));
+        assert!(lines[1].contains("::Refuses"));
+    }
+
+    #[test]
+    fn warmup_is_idempotent() {
+        let mut pool = quiet(WorkerPool::new(2));
+        pool.warmup(4);
+        pool.warmup(4);
+        pool.warmup(2);
+        assert_eq!(pool.warm.len(), 4);
+        pool.warmup(5);
+        assert_eq!(pool.warm.len(), 5);
+    }
+
+    #[test]
+    fn warmed_up_threads_run_every_batch() {
+        /// Records the thread it ran on.
+        struct RecordsThread(Arc<Mutex<HashSet<thread::ThreadId>>>);
+
+        impl TaskHandler for RecordsThread {
+            fn execute(&self) -> Result<i32, TaskError> {
+                self.0.lock().unwrap().insert(thread::current().id());
+                thread::sleep(Duration::from_millis(5));
+                Ok(0)
+            }
+        }
+
+        let threads_per_batch = |pool: &WorkerPool| {
+            (0..2)
+                .map(|_| {
+                    let threads = Arc::new(Mutex::new(HashSet::new()));
+                    for _ in 0..8 {
+                        pool.submit(Task::new(
+                            RecordsThread(Arc::clone(&threads)),
+                            PriorityLevel::Medium,
+                        ));
+                    }
+                    assert_eq!(pool.run().executed, 8);
+                    let threads = threads.lock().unwrap().clone();
+                    threads
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut warm = quiet(WorkerPool::new(2));
+        warm.warmup(2);
+        let warm_ids: HashSet<thread::ThreadId> = warm
+            .warm
+            .iter()
+            .map(|worker| worker.thread.as_ref().unwrap().thread().id())
+            .collect();
+        let batches = threads_per_batch(&warm);
+        assert!(batches.iter().all(|threads| threads.is_subset(&warm_ids)));
+
+        // Without warmup, every batch starts threads of its own.
+        let batches = threads_per_batch(&quiet(WorkerPool::new(2)));
+        assert!(batches[0].is_disjoint(&batches[1]));
+    }
+
+    #[test]
+    fn age_promoter_lifts_waiting_tasks_one_level() {
+        let pool = quiet(WorkerPool::new(1));
+        let low: Vec<Uuid> = (0..3)
+            .map(|_| {
+                let task = Task::new(Sleeps(Duration::ZERO), PriorityLevel::Low);
+                let id = task.id;
+                pool.submit(task);
+                id
+            })
+            .collect();
+        let high = Task::new(Sleeps(Duration::ZERO), PriorityLevel::High);
+        let high_id = high.id;
+        pool.submit(high);
+
+        pool.start_age_promoter(Duration::from_millis(20), Duration::from_millis(150));
+        thread::sleep(Duration::from_millis(200));
+
+        let queue = pool.queue();
+        let queue = queue.lock().unwrap();
+        for id in low {
+            assert_eq!(queue.priority_of(id), Some(PriorityLevel::Medium));
+        }
+        assert_eq!(queue.priority_of(high_id), Some(PriorityLevel::High));
+    }
+
+    /// Records which worker started each task.
+    fn record_workers(pool: WorkerPool) -> (WorkerPool, Arc<Mutex<HashMap<Uuid, usize>>>) {
+        let workers = Arc::new(Mutex::new(HashMap::new()));
+        let record = Arc::clone(&workers);
+        let pool = pool.with_policy(ExecutionPolicy {
+            on_start: Some(Arc::new(move |ctx: &TaskContext| {
+                record
+                    .lock()
+                    .unwrap()
+                    .insert(ctx.task_id, ctx.worker_id.unwrap());
+            })),
+            ..ExecutionPolicy::default()
+        });
+        (quiet(pool), workers)
+    }
+
+    fn preferring(worker_id: usize, sleep: Duration) -> Task {
+        let mut task = Task::new(Sleeps(sleep), PriorityLevel::Medium);
+        task.preferred_worker = Some(worker_id);
+        task
+    }
+
+    #[test]
+    fn tasks_run_on_their_preferred_worker() {
+        let (pool, workers) = record_workers(WorkerPool::new(3));
+        let pool = pool.with_affinity_timeout(Duration::from_secs(10));
+        let mut preferred = Vec::new();
+        for _ in 0..4 {
+            let task = preferring(2, Duration::from_millis(10));
+            preferred.push(task.id);
+            pool.submit(task);
+        }
+        pool.submit(Task::new(Sleeps(Duration::ZERO), PriorityLevel::Low));
+
+        assert_eq!(pool.run().executed, 5);
+        let workers = workers.lock().unwrap();
+        assert_eq!(workers.len(), 5);
+        assert!(preferred.iter().all(|id| workers[id] == 2));
+    }
+
+    #[test]
+    fn a_missing_preferred_worker_does_not_block_the_task() {
+        let (pool, workers) = record_workers(WorkerPool::new(2));
+        let pool = pool.with_affinity_timeout(Duration::from_secs(10));
+        let task = preferring(7, Duration::ZERO);
+        let id = task.id;
+        pool.submit(task);
+
+        let started = Instant::now();
+        assert_eq!(pool.run().executed, 1);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(workers.lock().unwrap()[&id] < 2);
+    }
+
+    #[test]
+    fn other_workers_take_over_after_the_affinity_timeout() {
+        let (pool, workers) = record_workers(WorkerPool::new(2));
+        let pool = pool.with_affinity_timeout(Duration::from_millis(20));
+        let busy = preferring(0, Duration::from_millis(300));
+        let waiting = preferring(0, Duration::ZERO);
+        let (busy_id, waiting_id) = (busy.id, waiting.id);
+        pool.submit(busy);
+        pool.submit(waiting);
+
+        assert_eq!(pool.run().executed, 2);
+        let workers = workers.lock().unwrap();
+        assert_eq!(workers[&busy_id], 0);
+        assert_eq!(workers[&waiting_id], 1);
+    }
+
+    #[test]
+    fn children_inherit_the_root_correlation_id() {
+        let pool = WorkerPool::new(1);
+        let submitter = pool.submitter();
+        let root = Task::new(Sleeps(Duration::ZERO), PriorityLevel::Medium);
+        let root_id = root.id;
+        pool.submit(root);
+
+        let mut chain = Vec::new();
+        for _ in 0..3 {
+            let parent = pool.queue().lock().unwrap().pop_simple().unwrap();
+            chain.push(parent.correlation_id);
+            let ctx = TaskContext::for_task(&parent);
+            submitter.submit(
+                &ctx,
+                Task::new(Sleeps(Duration::ZERO), PriorityLevel::Medium),
+            );
+        }
+
+        // The root has none of its own; its id becomes the chain's.
+        assert_eq!(chain, [None, Some(root_id), Some(root_id)]);
+        let last = pool.queue().lock().unwrap().pop_simple().unwrap();
+        assert_eq!(last.correlation_id, Some(root_id));
+    }
+
+    #[test]
+    fn scope_returns_only_after_its_tasks_and_workers_are_done() {
+        /// Holds a clone of the token until it is dropped with its task.
+        struct Holds(Arc<AtomicUsize>);
+
+        impl TaskHandler for Holds {
+            fn execute(&self) -> Result<i32, TaskError> {
+                thread::sleep(Duration::from_millis(20));
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(0)
+            }
+        }
+
+        let pool = quiet(WorkerPool::new(3));
+        let finished = Arc::new(AtomicUsize::new(0));
+        let ids = scope(&pool, |tasks| {
+            (0..6)
+                .map(|_| {
+                    let task = Task::new(Holds(Arc::clone(&finished)), PriorityLevel::Medium);
+                    let id = task.id;
+                    tasks.submit(task);
+                    id
+                })
+                .collect::<Vec<_>>()
+        });
+
+        assert_eq!(finished.load(Ordering::SeqCst), 6);
+        // Every handler, and every worker that could still hold one, is gone.
+        assert_eq!(Arc::strong_count(&finished), 1);
+        assert!(ids
+            .iter()
+            .all(|id| pool.status(*id) == Some(TaskStatus::Completed(0))));
+        assert!(pool.queue().lock().unwrap().is_empty());
+    }
+}
@@ -1,3 +1,8 @@
+pub trait KnowsSecretKey {
+    fn run(&self);
+}
+
+#[derive(Default)]
 pub struct Person;
 
 impl Person {
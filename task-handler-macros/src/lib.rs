@@ -0,0 +1,107 @@
+//! Attribute macros for `task_handler`, re-exported from it behind the
+//! `macros` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Ident, ItemFn, LitStr};
+
+/// Turns `fn name() -> Result<i32, TaskError>` into a unit struct `Name`
+/// that implements `TaskHandler` by calling the function, and `IntoTask`
+/// with the priority given as `#[task(priority = "high")]` (`"medium"` if
+/// omitted). The function itself is left in place.
+///
+/// ```ignore
+/// #[task(priority = "high")]
+/// fn send_report() -> Result<i32, TaskError> {
+///     Ok(0)
+/// }
+///
+/// queue.push(SendReport.into_task());
+/// ```
+#[proc_macro_attribute]
+pub fn task(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut priority = LitStr::new("medium", Span::call_site());
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("priority") {
+            priority = meta.value()?.parse()?;
+            Ok(())
+        } else {
+            Err(meta.error("unsupported task attribute, expected `priority`"))
+        }
+    });
+    parse_macro_input!(args with parser);
+
+    let function = parse_macro_input!(item as ItemFn);
+    match expand(&function, &priority) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(function: &ItemFn, priority: &LitStr) -> syn::Result<proc_macro2::TokenStream> {
+    let priority_level = match priority.value().to_ascii_lowercase().as_str() {
+        "high" => quote!(::task_handler::PriorityLevel::High),
+        "medium" => quote!(::task_handler::PriorityLevel::Medium),
+        "low" => quote!(::task_handler::PriorityLevel::Low),
+        _ => {
+            return Err(syn::Error::new(
+                priority.span(),
+                "priority must be \"high\", \"medium\" or \"low\"",
+            ))
+        }
+    };
+    if !function.sig.inputs.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &function.sig.inputs,
+            "task functions take no arguments",
+        ));
+    }
+
+    let vis = &function.vis;
+    let function_name = &function.sig.ident;
+    let struct_name = Ident::new(
+        &upper_camel_case(&function_name.to_string()),
+        function_name.span(),
+    );
+    let type_name = struct_name.to_string();
+
+    Ok(quote! {
+        #function
+
+        #vis struct #struct_name;
+
+        impl ::task_handler::TaskHandler for #struct_name {
+            fn execute(&self) -> ::core::result::Result<i32, ::task_handler::TaskError> {
+                #function_name()
+            }
+
+            fn handler_type_name(&self) -> &'static str {
+                #type_name
+            }
+
+            fn as_any(&self) -> ::core::option::Option<&dyn ::core::any::Any> {
+                ::core::option::Option::Some(self)
+            }
+        }
+
+        impl ::task_handler::IntoTask for #struct_name {
+            fn into_task(self) -> ::task_handler::Task {
+                ::task_handler::Task::new(self, #priority_level)
+            }
+        }
+    })
+}
+
+fn upper_camel_case(snake: &str) -> String {
+    snake
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,45 @@
+#![cfg(feature = "macros")]
+
+use task_handler::{
+    task, IntoTask, PriorityLevel, PriorityQueue, TaskError, TaskHandler, TaskQueue,
+};
+
+#[task(priority = "high")]
+fn send_report() -> Result<i32, TaskError> {
+    Ok(7)
+}
+
+#[task]
+fn refuse() -> Result<i32, TaskError> {
+    Err(TaskError::Skipped)
+}
+
+#[test]
+fn generated_handler_calls_the_function() {
+    assert_eq!(SendReport.execute(), Ok(7));
+    assert_eq!(Refuse.execute(), Err(TaskError::Skipped));
+    assert_eq!(SendReport.handler_type_name(), "SendReport");
+    assert!(SendReport
+        .as_any()
+        .is_some_and(|any| any.is::<SendReport>()));
+}
+
+#[test]
+fn into_task_uses_the_attribute_priority() {
+    assert_eq!(SendReport.into_task().priority_level, PriorityLevel::High);
+    assert_eq!(Refuse.into_task().priority_level, PriorityLevel::Medium);
+}
+
+#[test]
+fn generated_tasks_run_from_a_queue() {
+    let mut queue = PriorityQueue::new();
+    queue.push(Refuse.into_task());
+    queue.push(SendReport.into_task());
+
+    let first = queue.pop_simple().unwrap();
+    assert_eq!(first.handler.execute(), Ok(7));
+
+    let summary = queue.handle_blocking();
+    assert_eq!(summary.skipped, 1);
+    assert!(queue.is_empty());
+}